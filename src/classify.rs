@@ -0,0 +1,101 @@
+use crate::client::Session;
+use crate::errors::AppError;
+use crate::provider::json_payload;
+use crate::writer_config::ThoughtType;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+const CLASSIFY_PROMPT: &str = r#"Classify the thought below into exactly one category and suggest up to 3 short lowercase tags.
+
+Categories:
+- "Notes": general notes, facts, observations or reference material
+- "Project": an idea for something to build, a product, startup or hobby project
+- "Misc": anything that fits no other category
+- "Todo": a task, chore or reminder to act on
+- "Question": something to research or find out
+
+Respond with a single JSON object only, no markdown, with the fields "thought_type" (one of the categories) and "tags" (list of strings).
+
+Thought:
+"#;
+
+/// A type and tags suggested by the AI provider for a thought.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Classification {
+    thought_type: ThoughtType,
+    tags: Vec<String>,
+}
+
+impl Classification {
+    pub fn new(thought_type: ThoughtType, tags: Vec<String>) -> Classification {
+        Classification { thought_type, tags }
+    }
+
+    pub fn thought_type(&self) -> &ThoughtType {
+        &self.thought_type
+    }
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
+
+fn classification_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "thought_type": {
+                "type": "string",
+                "enum": ["Notes", "Project", "Misc", "Todo", "Question"]
+            },
+            "tags": {"type": "array", "items": {"type": "string"}}
+        },
+        "required": ["thought_type", "tags"]
+    })
+}
+
+/// Parses a model response, normalising tags to trimmed lowercase.
+pub fn parse_classification(text: &str) -> Result<Classification, AppError> {
+    let mut classification: Classification = serde_json::from_str(json_payload(text))?;
+    classification.tags = classification
+        .tags
+        .iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    Ok(classification)
+}
+
+/// Asks the configured provider to pick a type and tags for the content.
+pub fn classify(session: &Session, content: &str) -> Result<Classification, AppError> {
+    let prompt = format!("{}{}", CLASSIFY_PROMPT, content);
+    let response = session.structured_response(&prompt, &classification_schema())?;
+    parse_classification(&response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_classification() {
+        let classification =
+            parse_classification(r#"{"thought_type": "Project", "tags": [" Rust ", "CLI", ""]}"#)
+                .unwrap();
+        assert_eq!(classification.thought_type(), &ThoughtType::Project);
+        assert_eq!(classification.tags(), ["rust", "cli"]);
+    }
+
+    #[test]
+    fn test_parse_classification_code_fence() {
+        let classification =
+            parse_classification("```json\n{\"thought_type\": \"Todo\", \"tags\": []}\n```")
+                .unwrap();
+        assert_eq!(classification.thought_type(), &ThoughtType::Todo);
+    }
+
+    #[test]
+    fn test_parse_classification_unknown_type() {
+        let result = parse_classification(r#"{"thought_type": "Recipe", "tags": []}"#);
+        assert!(matches!(result, Err(AppError::Json(_))));
+    }
+}
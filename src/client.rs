@@ -1,99 +1,388 @@
 use crate::{
+    db_operations::{cached_response, save_cached_response, save_usage},
     errors::AppError,
-    reader_config::{AIClientConfig, AIClientDetails},
+    provider::{
+        StreamedResponse, embedding_body, embedding_vector, request_body, response_text,
+        response_usage, truncated,
+    },
+    reader_config::{AIClient, AIClientConfig, CacheConfig, Config, RetryConfig},
+    retry::{RetryError, parse_retry_after, retry},
 };
-use lettre::transport::smtp::response;
-use reqwest::blocking::{Client, Request};
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, Request, RequestBuilder, Response};
+use reqwest::header::{CONTENT_TYPE, RETRY_AFTER};
+use rusqlite::Connection;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::io::{self, BufRead, BufReader, Write};
+use std::time::Duration;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+pub fn build_client(config: &AIClientConfig) -> Result<Client, AppError> {
+    let client = Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs()))
+        .build()?;
+    Ok(client)
+}
 
 pub fn get_request(
     client: &Client,
     config: &AIClientConfig,
-    prompt: &String,
+    prompt: &str,
 ) -> Result<Request, AppError> {
-    let request = client
-        .post(config.ai_client().endpoint())
-        .header("x-goog-api-key", config.bearer_token())
-        .json(&prompt)
-        .build()?;
-    Ok(request)
-}
-
-pub fn send_request(client: &Client, request: Request) -> Result<String, AppError> {
-    let response = client.execute(request)?;
-    Ok(response.text()?)
-}
-
-pub fn get_response(config: &AIClientConfig, content: &[&String]) -> Result<String, AppError> {
-    let prompt = format!(
-        r#"I have a list of ideas - they could be startup ideas, product concepts, hobby projects, or experimental tools. For EACH idea below, provide relevant analysis and context.
-
-**IMPORTANT**: 
-- Assess the scale and nature of each idea (hobby project vs business venture vs research experiment)
-- Only include sections that are meaningful for that specific idea
-- If it's a hobby project, focus on learning opportunities, tech stack, and cool factor
-- If it's a business idea, focus on market and monetization
-- If unsure about something, say so
-- Skip sections that don't apply
-
-Consider including (when relevant):
-- **Idea Type & Scale**: Is this a hobby project, side hustle, startup, or enterprise product?
-- **What Makes This Interesting**: Why is this worth building or exploring?
-- **Technical Approach**: Key technologies, architecture decisions, implementation complexity
-- **Learning Opportunities**: New skills or concepts you'd gain (especially for hobby projects)
-- **Market Analysis**: Market size, competitors, target users (for business ideas)
-- **Business Model**: Revenue streams, monetization strategy (if applicable)
-- **Time & Resource Estimate**: Rough effort required (hours/days/weeks/months)
-- **Risks & Challenges**: Technical hurdles, market risks, or other obstacles
-- **Similar Projects/Inspiration**: Existing tools or projects to learn from
-- **Research Resources**: Relevant articles, papers, docs, or tutorials (with URLs when possible)
-- **Next Steps**: Concrete first actions to validate or build this
-
-**IDEAS:**
-
-{:?}
-
-For each idea:
-- Use clear headings like "=== IDEA #1: [brief description] ==="
-- Be practical and honest about feasibility
-- Tailor your analysis to the idea's nature and scale
-- Include any other insights particularly important for that idea"#,
-        content
-    );
-    let escaped_prompt = prompt
-        .replace('\\', "\\\\")
-        .replace('"', "\\\"")
-        .replace('\n', "\\n")
-        .replace('\r', "\\r")
-        .replace('\t', "\\t");
-
-    // Construct request body as a string
-    let request_body = format!(
-        r#"{{"contents":[{{"parts":[{{"text":"{}"}}]}}]}}"#,
-        escaped_prompt
-    );
-    let client = Client::new();
+    build_request(client, config, prompt, None, false)
+}
 
-    let response = client
-        .post(config.ai_client().endpoint())
-        .header("x-goog-api-key", config.bearer_token())
-        .header("Content-Type", "application/json")
-        .body(request_body)
-        .send()?;
+fn build_request(
+    client: &Client,
+    config: &AIClientConfig,
+    prompt: &str,
+    schema: Option<&Value>,
+    stream: bool,
+) -> Result<Request, AppError> {
+    let endpoint = if stream {
+        config.stream_endpoint()
+    } else {
+        config.endpoint()
+    };
+    let builder = client
+        .post(endpoint)
+        .header(CONTENT_TYPE, "application/json")
+        .body(request_body(config, prompt, schema, stream)?);
+    Ok(authorize(builder, config).build()?)
+}
 
-    // Debug: print status and raw response
+fn build_embedding_request(
+    client: &Client,
+    config: &AIClientConfig,
+    text: &str,
+) -> Result<Request, AppError> {
+    let builder = client
+        .post(config.embedding_endpoint()?)
+        .header(CONTENT_TYPE, "application/json")
+        .body(embedding_body(config, text)?);
+    Ok(authorize(builder, config).build()?)
+}
+
+/// Adds the provider's authentication headers.
+fn authorize(builder: RequestBuilder, config: &AIClientConfig) -> RequestBuilder {
+    match config.ai_client() {
+        AIClient::Gemini => builder.header("x-goog-api-key", config.bearer_token()),
+        AIClient::OpenAI => builder.header("Authorization", config.bearer_token()),
+        AIClient::Ollama if config.bearer_token().is_empty() => builder,
+        AIClient::Ollama => builder.header("Authorization", config.bearer_token()),
+        AIClient::Claude => builder
+            .header("x-api-key", config.bearer_token())
+            .header("anthropic-version", ANTHROPIC_VERSION),
+    }
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Executes the request, classifying failures for the retry policy: network
+/// errors and 408/429/5xx gateway statuses are transient, anything else is
+/// permanent.
+pub fn send_request(client: &Client, request: Request) -> Result<String, RetryError<AppError>> {
+    execute(client, request)?
+        .text()
+        .map_err(|e| RetryError::transient(e.into()))
+}
+
+/// Like [`send_request`], but reads the response as server-sent events,
+/// writing each piece of text to `out` as it arrives. Returns the assembled
+/// response in the provider's non-streaming format. A stream that breaks
+/// after text was written is not retried, as that would print it twice.
+pub fn send_streaming_request<W: Write>(
+    client: &Client,
+    request: Request,
+    ai_client: &AIClient,
+    out: &mut W,
+) -> Result<String, RetryError<AppError>> {
+    let response = execute(client, request)?;
+    let mut streamed = StreamedResponse::default();
+    let mut emitted = false;
+    for line in BufReader::new(response).lines() {
+        let line = line.map_err(|e| {
+            if emitted {
+                RetryError::permanent(e.into())
+            } else {
+                RetryError::transient(e.into())
+            }
+        })?;
+        let Some(data) = line.strip_prefix("data:") else {
+            continue;
+        };
+        let text = streamed
+            .push(ai_client, data.trim())
+            .map_err(RetryError::permanent)?;
+        write!(out, "{}", text)
+            .and_then(|_| out.flush())
+            .map_err(|e| RetryError::permanent(e.into()))?;
+        emitted |= !text.is_empty();
+    }
+    writeln!(out).map_err(|e| RetryError::permanent(e.into()))?;
+    Ok(streamed.into_body(ai_client))
+}
+
+/// Sends the request and returns the response if its status is a success.
+fn execute(client: &Client, request: Request) -> Result<Response, RetryError<AppError>> {
+    let response = client
+        .execute(request)
+        .map_err(|e| RetryError::transient(e.into()))?;
     let status = response.status();
-    let response_text = response.text()?;
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after);
+    let body = response
+        .text()
+        .map_err(|e| RetryError::transient(e.into()))?;
+    if is_transient_status(status) {
+        Err(
+            RetryError::transient(AppError::Reqwest(format!("{}: {}", status, body)))
+                .with_retry_after(retry_after),
+        )
+    } else {
+        Err(RetryError::permanent(AppError::Reqwest(format!(
+            "{}: {}",
+            status, body
+        ))))
+    }
+}
 
-    println!("Status: {}", status);
-    println!("Raw response: {}", response_text);
+/// Sends a request built by `build`, retrying transient failures, and
+/// returns the raw response body.
+fn send_with_retry<F>(
+    config: &AIClientConfig,
+    retry_config: &RetryConfig,
+    build: F,
+) -> Result<String, AppError>
+where
+    F: Fn(&Client) -> Result<Request, AppError>,
+{
+    let client = build_client(config)?;
+    retry(retry_config, || {
+        let request = build(&client).map_err(RetryError::permanent)?;
+        send_request(&client, request)
+    })
+}
 
-    Ok(response_text)
-    /*
-        let client = Client::new();
-        let request = get_request(&client, config, &request_body)?;
-        let response = send_request(&client, request)?;
-        Ok(response)
-    */
+fn complete(
+    config: &AIClientConfig,
+    retry_config: &RetryConfig,
+    prompt: &str,
+    schema: Option<&Value>,
+) -> Result<String, AppError> {
+    send_with_retry(config, retry_config, |client| {
+        build_request(client, config, prompt, schema, false)
+    })
+}
+
+/// Streams a completion to stdout, retrying transient failures before the
+/// stream starts or while it is read.
+fn complete_streaming(
+    config: &AIClientConfig,
+    retry_config: &RetryConfig,
+    prompt: &str,
+    schema: Option<&Value>,
+) -> Result<String, AppError> {
+    let client = build_client(config)?;
+    retry(retry_config, || {
+        let request =
+            build_request(&client, config, prompt, schema, true).map_err(RetryError::permanent)?;
+        send_streaming_request(&client, request, config.ai_client(), &mut io::stdout())
+    })
+}
+
+fn embed(
+    config: &AIClientConfig,
+    retry_config: &RetryConfig,
+    text: &str,
+) -> Result<String, AppError> {
+    send_with_retry(config, retry_config, |client| {
+        build_embedding_request(client, config, text)
+    })
+}
+
+/// Sends the prompt to the configured provider and returns the generated
+/// text, retrying transient failures according to the retry policy.
+pub fn get_response(
+    config: &AIClientConfig,
+    retry_config: &RetryConfig,
+    prompt: &str,
+) -> Result<String, AppError> {
+    response_text(
+        config.ai_client(),
+        &complete(config, retry_config, prompt, None)?,
+    )
+}
+
+/// Like [`get_response`], but asks the provider for JSON conforming to
+/// `schema`. The returned text still needs validating by the caller.
+pub fn get_structured_response(
+    config: &AIClientConfig,
+    retry_config: &RetryConfig,
+    prompt: &str,
+    schema: &Value,
+) -> Result<String, AppError> {
+    let body = complete(config, retry_config, prompt, Some(schema))?;
+    response_text(config.ai_client(), &body)
+}
+
+/// Computes the embedding vector for `text` with the configured embedding
+/// model, retrying transient failures.
+pub fn get_embedding(
+    config: &AIClientConfig,
+    retry_config: &RetryConfig,
+    text: &str,
+) -> Result<Vec<f32>, AppError> {
+    embedding_vector(config.ai_client(), &embed(config, retry_config, text)?)
+}
+
+/// Hex SHA-256 of the provider, model, schema and prompt, identifying a
+/// completion request in the response cache.
+pub fn cache_key(config: &AIClientConfig, prompt: &str, schema: Option<&Value>) -> String {
+    let mut hasher = Sha256::new();
+    for part in [
+        format!("{:?}", config.ai_client()),
+        config.model().to_string(),
+        schema.map(Value::to_string).unwrap_or_default(),
+        prompt.to_string(),
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Provider calls made on behalf of the reader or writer. Unlike the free
+/// functions, every call records its token usage in the database, and with a
+/// cache, completions are answered from earlier responses to the same prompt.
+/// Streaming sessions print completions to the terminal as they arrive.
+pub struct Session<'a> {
+    conn: &'a Connection,
+    config: AIClientConfig,
+    retry_config: &'a RetryConfig,
+    cache_ttl_hours: Option<u64>,
+    streaming: bool,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(
+        conn: &'a Connection,
+        config: AIClientConfig,
+        retry_config: &'a RetryConfig,
+    ) -> Session<'a> {
+        Session {
+            conn,
+            config,
+            retry_config,
+            cache_ttl_hours: None,
+            streaming: false,
+        }
+    }
+
+    /// A session for the main AI provider, with the configured cache and
+    /// streaming when verbose.
+    pub fn from_config(conn: &'a Connection, config: &'a Config) -> Session<'a> {
+        Session::new(conn, config.ai_client_config(), config.retry())
+            .with_cache(config.cache())
+            .with_streaming(config.verbose())
+    }
+
+    pub fn with_cache(mut self, cache: &CacheConfig) -> Self {
+        self.cache_ttl_hours = cache.enabled().then_some(cache.ttl_hours());
+        self
+    }
+
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    pub fn config(&self) -> &AIClientConfig {
+        &self.config
+    }
+
+    fn record(&self, body: &str) -> Result<(), AppError> {
+        if let Some(tokens) = response_usage(self.config.ai_client(), body) {
+            save_usage(
+                self.conn,
+                &format!("{:?}", self.config.ai_client()),
+                self.config.model(),
+                &tokens,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Sends a completion request unless a cached response is still fresh.
+    /// Cached responses cost nothing, so they record no usage. A response cut
+    /// off at the output token limit is an [`AppError::Truncated`] error and
+    /// is not cached.
+    fn completion(&self, prompt: &str, schema: Option<&Value>) -> Result<String, AppError> {
+        let key = cache_key(&self.config, prompt, schema);
+        if let Some(ttl_hours) = self.cache_ttl_hours
+            && let Some(body) = cached_response(self.conn, &key, ttl_hours)?
+        {
+            return Ok(body);
+        }
+        let body = if self.streaming {
+            complete_streaming(&self.config, self.retry_config, prompt, schema)?
+        } else {
+            complete(&self.config, self.retry_config, prompt, schema)?
+        };
+        self.record(&body)?;
+        if truncated(self.config.ai_client(), &body) {
+            return Err(AppError::Truncated(format!(
+                "{:?} reached the limit of {} output tokens",
+                self.config.ai_client(),
+                self.config.max_output_tokens()
+            )));
+        }
+        if self.cache_ttl_hours.is_some() {
+            save_cached_response(self.conn, &key, &body)?;
+        }
+        Ok(body)
+    }
+
+    /// See [`get_response`].
+    pub fn response(&self, prompt: &str) -> Result<String, AppError> {
+        let body = self.completion(prompt, None)?;
+        response_text(self.config.ai_client(), &body)
+    }
+
+    /// See [`get_structured_response`].
+    pub fn structured_response(&self, prompt: &str, schema: &Value) -> Result<String, AppError> {
+        let body = self.completion(prompt, Some(schema))?;
+        response_text(self.config.ai_client(), &body)
+    }
+
+    /// See [`get_embedding`].
+    pub fn embedding(&self, text: &str) -> Result<Vec<f32>, AppError> {
+        let body = embed(&self.config, self.retry_config, text)?;
+        self.record(&body)?;
+        embedding_vector(self.config.ai_client(), &body)
+    }
 }
 
 #[cfg(test)]
@@ -188,4 +477,141 @@ mod tests {
             crate::reader_config::AIClient::Gemini
         ));
     }
+
+    fn create_provider_config(ai_client: &str, extra: &str) -> AIClientConfig {
+        serde_json::from_str(&format!(
+            r#"{{
+                "bearer_token": "secret",
+                "ai_client": "{}"{}
+            }}"#,
+            ai_client, extra
+        ))
+        .unwrap()
+    }
+
+    fn request_body_json(request: &Request) -> serde_json::Value {
+        let bytes = request.body().unwrap().as_bytes().unwrap();
+        serde_json::from_slice(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_get_request_gemini_generation_config() {
+        let config = create_provider_config(
+            "Gemini",
+            r#", "model": "gemini-2.5-pro", "temperature": 0.25, "max_output_tokens": 256"#,
+        );
+        let client = Client::new();
+
+        let request = get_request(&client, &config, "Hello \"world\"\n").unwrap();
+
+        assert!(
+            request
+                .url()
+                .as_str()
+                .contains("gemini-2.5-pro:generateContent")
+        );
+        let body = request_body_json(&request);
+        assert_eq!(body["contents"][0]["parts"][0]["text"], "Hello \"world\"\n");
+        assert_eq!(body["generationConfig"]["temperature"], 0.25);
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 256);
+    }
+
+    #[test]
+    fn test_get_request_openai_headers_and_body() {
+        let config = create_provider_config("OpenAI", r#", "model": "gpt-4.1""#);
+        let client = Client::new();
+
+        let request = get_request(&client, &config, "Test").unwrap();
+
+        assert_eq!(
+            request.url().as_str(),
+            "https://api.openai.com/v1/chat/completions"
+        );
+        assert_eq!(request.headers().get("Authorization").unwrap(), "secret");
+        let body = request_body_json(&request);
+        assert_eq!(body["model"], "gpt-4.1");
+        assert_eq!(body["messages"][0]["content"], "Test");
+        assert_eq!(body["max_tokens"], 4096);
+    }
+
+    #[test]
+    fn test_get_request_claude_headers_and_body() {
+        let config = create_provider_config("Claude", r#", "max_output_tokens": 1024"#);
+        let client = Client::new();
+
+        let request = get_request(&client, &config, "Test").unwrap();
+
+        assert_eq!(
+            request.url().as_str(),
+            "https://api.anthropic.com/v1/messages"
+        );
+        assert_eq!(request.headers().get("x-api-key").unwrap(), "secret");
+        assert_eq!(
+            request.headers().get("anthropic-version").unwrap(),
+            ANTHROPIC_VERSION
+        );
+        let body = request_body_json(&request);
+        assert_eq!(body["model"], "claude-3-5-haiku-latest");
+        assert_eq!(body["max_tokens"], 1024);
+    }
+
+    #[test]
+    fn test_get_request_honours_base_url() {
+        let config = create_provider_config("Gemini", r#", "base_url": "http://127.0.0.1:8080""#);
+        let client = Client::new();
+
+        let request = get_request(&client, &config, "Test").unwrap();
+
+        assert_eq!(
+            request.url().as_str(),
+            "http://127.0.0.1:8080/v1/models/gemini-2.0-flash:generateContent"
+        );
+    }
+
+    #[test]
+    fn test_get_request_ollama_uses_openai_format() {
+        let config = create_provider_config("Ollama", r#", "model": "llama3.1:8b""#);
+        let client = Client::new();
+
+        let request = get_request(&client, &config, "Test").unwrap();
+
+        assert_eq!(
+            request.url().as_str(),
+            "http://localhost:11434/v1/chat/completions"
+        );
+        assert_eq!(request.headers().get("Authorization").unwrap(), "secret");
+        let body = request_body_json(&request);
+        assert_eq!(body["model"], "llama3.1:8b");
+        assert_eq!(body["messages"][0]["role"], "user");
+    }
+
+    #[test]
+    fn test_cache_key_covers_provider_model_and_prompt() {
+        let gemini = create_provider_config("Gemini", "");
+        let key = cache_key(&gemini, "Idea", None);
+
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, cache_key(&gemini, "Idea", None));
+        assert_ne!(key, cache_key(&gemini, "Other idea", None));
+        assert_ne!(
+            key,
+            cache_key(&gemini, "Idea", Some(&serde_json::json!({})))
+        );
+        let other_model = create_provider_config("Gemini", r#", "model": "gemini-2.5-pro""#);
+        assert_ne!(key, cache_key(&other_model, "Idea", None));
+        assert_ne!(
+            key,
+            cache_key(&create_provider_config("OpenAI", ""), "Idea", None)
+        );
+    }
+
+    #[test]
+    fn test_get_request_ollama_without_token_skips_auth() {
+        let config: AIClientConfig = serde_json::from_str(r#"{"ai_client": "Ollama"}"#).unwrap();
+        let client = Client::new();
+
+        let request = get_request(&client, &config, "Test").unwrap();
+
+        assert!(request.headers().get("Authorization").is_none());
+    }
 }
@@ -0,0 +1,1386 @@
+use crate::classify::{self, Classification};
+use crate::client::Session;
+use crate::db_operations::{
+    accept_suggestion, add_link, add_subtasks, backlinks, count_thoughts, descendants,
+    edit_thought, empty_trash, get_thought, is_descendant, links_from, list_thoughts, list_todos,
+    open_questions, pending_suggestions, progress, progress_of, rate_thought, reject_suggestion,
+    remove_link, save_suggestion, set_answered, set_parent, set_state, toggle_pinned,
+    toggle_starred, unclassified, unreview, unreview_matching, update_todo,
+};
+use crate::embeddings;
+use crate::errors::AppError;
+use crate::ics;
+use crate::links::LinkKind;
+use crate::reader_config::{Args, Command, ExportFormat, OutputFormat, ThoughtFilter, TodoCommand};
+use crate::subtasks;
+use crate::thought::{Thought, ThoughtState};
+use crate::todo::{self, Priority, TodoStatus};
+use crate::usage;
+use crate::writer_config::ThoughtType;
+use chrono::Utc;
+use rusqlite::Connection;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process;
+
+/// Runs a reader subcommand. `args` gives access to the config file for
+/// commands that talk to the AI provider.
+pub fn run<W: Write>(
+    command: &Command,
+    args: &Args,
+    conn: &Connection,
+    out: &mut W,
+) -> Result<(), AppError> {
+    match command {
+        Command::Show { id } => show(conn, *id, out),
+        Command::List { filter, format } => list(conn, filter, *format, out),
+        Command::Edit {
+            id,
+            content,
+            thought_type,
+        } => edit(conn, *id, content.as_deref(), thought_type.as_ref(), out),
+        Command::Archive { ids } => move_to(conn, ids, ThoughtState::Archived, "Archived", out),
+        Command::Trash { ids } => move_to(conn, ids, ThoughtState::Trashed, "Trashed", out),
+        Command::Restore { ids } => move_to(conn, ids, ThoughtState::Active, "Restored", out),
+        Command::EmptyTrash => {
+            let deleted = empty_trash(conn)?;
+            writeln!(out, "Deleted {} trashed thoughts", deleted)?;
+            Ok(())
+        }
+        Command::Reclassify {
+            thought_type,
+            limit,
+        } => reclassify(args, conn, thought_type, *limit, out),
+        Command::Unreview { ids, filter } => requeue(conn, ids, filter, out),
+        Command::Done { ids } => {
+            for id in ids {
+                if update_todo(conn, *id, Some(TodoStatus::Done), None, false, None)? {
+                    writeln!(out, "Done #{}", id)?;
+                } else {
+                    writeln!(out, "#{} is not a todo", id)?;
+                }
+            }
+            Ok(())
+        }
+        Command::Todo { command } => todos(conn, command, out),
+        Command::Export { format } => export(conn, format, out),
+        Command::Link {
+            source,
+            target,
+            kind,
+            remove,
+        } => link(conn, *source, *target, *kind, *remove, out),
+        Command::Parent { id, parent } => reparent(conn, *id, *parent, out),
+        Command::Pin { ids } => toggle(conn, ids, toggle_pinned, ["Pinned", "Unpinned"], out),
+        Command::Star { ids } => toggle(conn, ids, toggle_starred, ["Starred", "Unstarred"], out),
+        Command::Subtasks { id, ai } => break_down(args, conn, *id, *ai, out),
+        Command::Rate { id, quality } => rate(conn, *id, *quality, out),
+        Command::Suggestions => suggestions(conn, out),
+        Command::Similar { id, limit } => similar(args, conn, *id, *limit, out),
+        Command::Questions => questions(conn, out),
+        Command::Usage { month, all } => {
+            usage::report(conn, args.config()?.usage(), month.as_deref(), *all, out)
+        }
+        Command::Answered { ids, reopen } => answered(conn, ids, !*reopen, out),
+        Command::Accept { ids } => decide(conn, ids, accept_suggestion, "Accepted", out),
+        Command::Reject { ids } => decide(conn, ids, reject_suggestion, "Rejected", out),
+    }
+}
+
+/// Lets the user edit `content` in `editor`, a command that may carry its own
+/// arguments, e.g. `code --wait`. Returns the saved text without the trailing
+/// newline editors add.
+pub fn edit_in_editor(editor: &str, content: &str) -> Result<String, AppError> {
+    let mut words = editor.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| AppError::Config("EDITOR is empty".to_string()))?;
+    let path = env::temp_dir().join(format!("thought-edit-{}.txt", process::id()));
+    fs::write(&path, content)?;
+    let status = process::Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status();
+    let edited = fs::read_to_string(&path);
+    fs::remove_file(&path)?;
+    let status = status?;
+    if !status.success() {
+        return Err(AppError::IO(format!("{} exited with {}", editor, status)));
+    }
+    Ok(edited?.trim_end_matches(['\n', '\r']).to_string())
+}
+
+/// Changes a thought's content and/or type. Without either, the content is
+/// edited in `$VISUAL` or `$EDITOR`, falling back to `vi`.
+pub fn edit<W: Write>(
+    conn: &Connection,
+    id: i32,
+    content: Option<&str>,
+    thought_type: Option<&ThoughtType>,
+    out: &mut W,
+) -> Result<(), AppError> {
+    let thought =
+        get_thought(conn, id)?.ok_or_else(|| AppError::NotFound(format!("thought #{}", id)))?;
+    let content = match (content, thought_type) {
+        (Some(content), _) => Some(content.to_string()),
+        (None, Some(_)) => None,
+        (None, None) => {
+            let editor = env::var("VISUAL")
+                .or_else(|_| env::var("EDITOR"))
+                .unwrap_or_else(|_| "vi".to_string());
+            Some(edit_in_editor(&editor, thought.content())?)
+        }
+    };
+    let content = content.filter(|c| c != thought.content());
+    let thought_type = thought_type.filter(|t| *t != thought.thought_type());
+    if content.as_deref().is_some_and(|c| c.trim().is_empty()) {
+        writeln!(out, "Content cannot be empty, #{} left unchanged", id)?;
+    } else if content.is_none() && thought_type.is_none() {
+        writeln!(out, "No changes to #{}", id)?;
+    } else {
+        edit_thought(conn, id, thought_type, content.as_deref())?;
+        writeln!(out, "Updated #{}", id)?;
+    }
+    Ok(())
+}
+
+/// Puts thoughts back into the next digest, the given ids or, without ids,
+/// every reviewed thought matching the filter.
+fn requeue<W: Write>(
+    conn: &Connection,
+    ids: &[i32],
+    filter: &ThoughtFilter,
+    out: &mut W,
+) -> Result<(), AppError> {
+    if ids.is_empty() {
+        let count = unreview_matching(conn, filter)?;
+        writeln!(out, "Queued {} thoughts for the next digest", count)?;
+    }
+    for id in ids {
+        if unreview(conn, *id)? {
+            writeln!(out, "Queued #{} for the next digest", id)?;
+        } else {
+            writeln!(out, "#{} is not a reviewed thought", id)?;
+        }
+    }
+    Ok(())
+}
+
+fn days(count: u32) -> String {
+    if count == 1 {
+        "1 day".to_string()
+    } else {
+        format!("{} days", count)
+    }
+}
+
+/// Records a review rating and prints when the thought is next due.
+pub fn rate<W: Write>(
+    conn: &Connection,
+    id: i32,
+    quality: u8,
+    out: &mut W,
+) -> Result<(), AppError> {
+    if !rate_thought(conn, id, quality)? {
+        return Err(AppError::NotFound(format!("thought #{}", id)));
+    }
+    if let Some(schedule) = get_thought(conn, id)?.as_ref().and_then(Thought::schedule) {
+        writeln!(
+            out,
+            "Rated #{}, next review on {} (in {})",
+            id,
+            schedule.next_review_on(),
+            days(schedule.interval_days())
+        )?;
+    }
+    Ok(())
+}
+
+fn move_to<W: Write>(
+    conn: &Connection,
+    ids: &[i32],
+    state: ThoughtState,
+    verb: &str,
+    out: &mut W,
+) -> Result<(), AppError> {
+    for id in ids {
+        if set_state(conn, *id, state)? {
+            writeln!(out, "{} #{}", verb, id)?;
+        } else {
+            writeln!(out, "No thought #{}", id)?;
+        }
+    }
+    Ok(())
+}
+
+/// Flips a flag on each thought, printing the first verb when it is now set
+/// and the second when it is now cleared.
+fn toggle<W: Write>(
+    conn: &Connection,
+    ids: &[i32],
+    flip: fn(&Connection, i32) -> Result<Option<bool>, AppError>,
+    [on, off]: [&str; 2],
+    out: &mut W,
+) -> Result<(), AppError> {
+    for id in ids {
+        match flip(conn, *id)? {
+            Some(true) => writeln!(out, "{} #{}", on, id)?,
+            Some(false) => writeln!(out, "{} #{}", off, id)?,
+            None => writeln!(out, "No thought #{}", id)?,
+        }
+    }
+    Ok(())
+}
+
+const CONTENT_WIDTH: usize = 60;
+
+/// The content on one line, cut to `width` characters.
+fn preview(content: &str, width: usize) -> String {
+    let line = content.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if line.chars().count() > width {
+        format!("{}...", line.chars().take(width - 3).collect::<String>())
+    } else {
+        line
+    }
+}
+
+/// Prints aligned columns under `header`: ids right-aligned, the last
+/// column unpadded.
+fn print_columns<W: Write, const N: usize>(
+    header: [&str; N],
+    rows: &[[String; N]],
+    out: &mut W,
+) -> Result<(), AppError> {
+    let header = header.map(String::from);
+    let mut widths = [0; N];
+    for row in std::iter::once(&header).chain(rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (cell, width))| match i {
+                0 => format!("{:>width$}", cell),
+                _ if i == N - 1 => cell.clone(),
+                _ => format!("{:<width$}", cell),
+            })
+            .collect::<Vec<String>>()
+            .join("  ");
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// Prints thoughts with their depth in the tree, sub-thoughts indented under
+/// their parents and parents followed by their progress. Starred thoughts get
+/// a star after their type.
+fn print_table<W: Write>(thoughts: &[(usize, Thought)], out: &mut W) -> Result<(), AppError> {
+    let rows: Vec<[String; 6]> = thoughts
+        .iter()
+        .map(|(depth, t)| {
+            let mut content = format!(
+                "{}{}",
+                "  ".repeat(*depth),
+                preview(t.content(), CONTENT_WIDTH)
+            );
+            if let Some(progress) = t.progress() {
+                content.push_str(&format!(" [{}]", progress));
+            }
+            let mut thought_type = t.thought_type().to_string();
+            if t.starred() {
+                thought_type.push_str(" ★");
+            }
+            [
+                t.id().to_string(),
+                thought_type,
+                t.created_on().to_string(),
+                if t.reviewed() { "yes" } else { "no" }.to_string(),
+                t.tags().join(","),
+                content,
+            ]
+        })
+        .collect();
+    let header = ["ID", "Type", "Captured", "Reviewed", "Tags", "Content"];
+    print_columns(header, &rows, out)
+}
+
+fn print_todos<W: Write>(thoughts: &[Thought], out: &mut W) -> Result<(), AppError> {
+    let today = todo::today();
+    let rows: Vec<[String; 5]> = thoughts
+        .iter()
+        .filter_map(|t| t.todo().map(|details| (t, details)))
+        .map(|(t, details)| {
+            let due = match details.due() {
+                Some(due) if details.overdue(today) => format!("{} (overdue)", due),
+                Some(due) => due.to_string(),
+                None => String::new(),
+            };
+            [
+                t.id().to_string(),
+                details.status().to_string(),
+                details
+                    .priority()
+                    .map(|p| p.to_string())
+                    .unwrap_or_default(),
+                due,
+                preview(t.content(), CONTENT_WIDTH),
+            ]
+        })
+        .collect();
+    print_columns(["ID", "Status", "Priority", "Due", "Content"], &rows, out)
+}
+
+/// Lists or updates todos.
+pub fn todos<W: Write>(
+    conn: &Connection,
+    command: &TodoCommand,
+    out: &mut W,
+) -> Result<(), AppError> {
+    match command {
+        TodoCommand::List {
+            overdue,
+            status,
+            format,
+        } => {
+            let yesterday = todo::today().pred_opt().map(todo::date);
+            let due_by = if *overdue { yesterday.as_deref() } else { None };
+            let todos = list_todos(conn, *status, due_by)?;
+            match format {
+                OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(&todos)?)?,
+                OutputFormat::Ndjson => {
+                    for thought in &todos {
+                        writeln!(out, "{}", serde_json::to_string(thought)?)?;
+                    }
+                }
+                OutputFormat::Table if todos.is_empty() => writeln!(out, "No todos found")?,
+                OutputFormat::Table => print_todos(&todos, out)?,
+            }
+        }
+        TodoCommand::Set {
+            id,
+            status,
+            due,
+            no_due,
+            priority,
+        } => set_todo(conn, *id, *status, due.as_deref(), *no_due, *priority, out)?,
+    }
+    Ok(())
+}
+
+/// Writes the export to its output file, or to `out` without one.
+pub fn export<W: Write>(
+    conn: &Connection,
+    format: &ExportFormat,
+    out: &mut W,
+) -> Result<(), AppError> {
+    match format {
+        ExportFormat::Ics { events, output } => {
+            let todos = list_todos(conn, None, None)?;
+            let calendar = ics::calendar(&todos, Utc::now(), *events);
+            match output {
+                Some(path) => {
+                    fs::write(path, calendar)?;
+                    writeln!(out, "Exported {} todos to {}", todos.len(), path.display())?;
+                }
+                None => out.write_all(calendar.as_bytes())?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn set_todo<W: Write>(
+    conn: &Connection,
+    id: i32,
+    status: Option<TodoStatus>,
+    due: Option<&str>,
+    clear_due: bool,
+    priority: Option<Priority>,
+    out: &mut W,
+) -> Result<(), AppError> {
+    if status.is_none() && due.is_none() && !clear_due && priority.is_none() {
+        writeln!(out, "No changes to #{}", id)?;
+    } else if update_todo(conn, id, status, due, clear_due, priority)? {
+        writeln!(out, "Updated #{}", id)?;
+    } else {
+        writeln!(out, "#{} is not a todo", id)?;
+    }
+    Ok(())
+}
+
+/// Prints one page of the thoughts matching the filter. With `--tree` the
+/// page holds top-level thoughts, each followed by its sub-thoughts.
+pub fn list<W: Write>(
+    conn: &Connection,
+    filter: &ThoughtFilter,
+    format: OutputFormat,
+    out: &mut W,
+) -> Result<(), AppError> {
+    let mut thoughts = Vec::new();
+    for thought in list_thoughts(conn, filter)? {
+        let id = *thought.id();
+        thoughts.push((0, thought));
+        if filter.tree() {
+            thoughts.extend(descendants(conn, id, filter.state())?);
+        }
+    }
+    let ids: Vec<i32> = thoughts.iter().map(|(_, t)| *t.id()).collect();
+    let mut progress = progress_of(conn, &ids)?;
+    for (_, thought) in thoughts.iter_mut() {
+        if let Some(progress) = progress.remove(thought.id()) {
+            thought.set_progress(progress);
+        }
+    }
+    match format {
+        OutputFormat::Json => {
+            let flat: Vec<&Thought> = thoughts.iter().map(|(_, t)| t).collect();
+            writeln!(out, "{}", serde_json::to_string_pretty(&flat)?)?
+        }
+        OutputFormat::Ndjson => {
+            for (_, thought) in &thoughts {
+                writeln!(out, "{}", serde_json::to_string(thought)?)?;
+            }
+        }
+        OutputFormat::Table => {
+            let total = count_thoughts(conn, filter)?;
+            if thoughts.is_empty() {
+                writeln!(out, "No thoughts found")?;
+            } else {
+                print_table(&thoughts, out)?;
+            }
+            if total > 0 {
+                writeln!(
+                    out,
+                    "Page {} of {} ({} thoughts)",
+                    filter.page(),
+                    total.div_ceil(filter.limit()),
+                    total
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn suggestion_line(classification: &Classification) -> String {
+    if classification.tags().is_empty() {
+        classification.thought_type().to_string()
+    } else {
+        format!(
+            "{} (tags: {})",
+            classification.thought_type(),
+            classification.tags().join(", ")
+        )
+    }
+}
+
+/// Classifies thoughts that have never been classified. A failure on one
+/// thought is reported and the rest are still attempted.
+pub fn reclassify<W: Write>(
+    args: &Args,
+    conn: &Connection,
+    thought_type: &ThoughtType,
+    limit: u32,
+    out: &mut W,
+) -> Result<(), AppError> {
+    let thoughts = unclassified(conn, thought_type, limit)?;
+    if thoughts.is_empty() {
+        writeln!(out, "No {} thoughts to classify", thought_type)?;
+        return Ok(());
+    }
+    let config = args.config()?;
+    let session = Session::from_config(conn, &config);
+    for thought in &thoughts {
+        match classify::classify(&session, thought.content()) {
+            Ok(classification) => {
+                save_suggestion(conn, *thought.id(), &classification)?;
+                writeln!(
+                    out,
+                    "#{}: {} -> {}",
+                    thought.id(),
+                    thought.thought_type(),
+                    suggestion_line(&classification)
+                )?;
+            }
+            Err(err) => eprintln!("Could not classify thought #{}: {}", thought.id(), err),
+        }
+    }
+    Ok(())
+}
+
+pub fn suggestions<W: Write>(conn: &Connection, out: &mut W) -> Result<(), AppError> {
+    let thoughts = pending_suggestions(conn)?;
+    if thoughts.is_empty() {
+        writeln!(out, "No pending suggestions")?;
+    }
+    for thought in &thoughts {
+        if let Some(suggestion) = thought.suggestion() {
+            writeln!(
+                out,
+                "#{} [{}] -> {}\n    {}",
+                thought.id(),
+                thought.thought_type(),
+                suggestion_line(suggestion),
+                thought.content()
+            )?;
+        }
+    }
+    Ok(())
+}
+
+pub fn similar<W: Write>(
+    args: &Args,
+    conn: &Connection,
+    id: i32,
+    limit: usize,
+    out: &mut W,
+) -> Result<(), AppError> {
+    let matches = embeddings::similar(conn, &args.config()?, id, limit)?;
+    if matches.is_empty() {
+        writeln!(out, "No similar thoughts")?;
+    }
+    for (thought, similarity) in matches {
+        writeln!(
+            out,
+            "{:.2}  #{} [{}] {}",
+            similarity,
+            thought.id(),
+            thought.thought_type(),
+            thought.content()
+        )?;
+    }
+    Ok(())
+}
+
+pub fn questions<W: Write>(conn: &Connection, out: &mut W) -> Result<(), AppError> {
+    let questions = open_questions(conn)?;
+    if questions.is_empty() {
+        writeln!(out, "No open questions")?;
+    }
+    for question in &questions {
+        writeln!(
+            out,
+            "#{} ({}) {}{}",
+            question.id(),
+            question.created_on(),
+            question.content(),
+            if question.answer().is_some() {
+                " [has answer]"
+            } else {
+                ""
+            }
+        )?;
+    }
+    Ok(())
+}
+
+fn answered<W: Write>(
+    conn: &Connection,
+    ids: &[i32],
+    answered: bool,
+    out: &mut W,
+) -> Result<(), AppError> {
+    for id in ids {
+        if !set_answered(conn, *id, answered)? {
+            writeln!(out, "#{} is not a question", id)?;
+        } else if answered {
+            writeln!(out, "Marked #{} answered", id)?;
+        } else {
+            writeln!(out, "Reopened #{}", id)?;
+        }
+    }
+    Ok(())
+}
+
+fn decide<W: Write>(
+    conn: &Connection,
+    ids: &[i32],
+    apply: fn(&Connection, i32) -> Result<bool, AppError>,
+    verb: &str,
+    out: &mut W,
+) -> Result<(), AppError> {
+    for id in ids {
+        if apply(conn, *id)? {
+            writeln!(out, "{} suggestion for #{}", verb, id)?;
+        } else {
+            writeln!(out, "No pending suggestion for #{}", id)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn show<W: Write>(conn: &Connection, id: i32, out: &mut W) -> Result<(), AppError> {
+    let thought =
+        get_thought(conn, id)?.ok_or_else(|| AppError::NotFound(format!("thought #{}", id)))?;
+    writeln!(
+        out,
+        "#{} [{}]{}{}{}{}{}",
+        thought.id(),
+        thought.thought_type(),
+        match thought.state() {
+            ThoughtState::Active => String::new(),
+            state => format!(" ({})", state),
+        },
+        if thought.pinned() { " (pinned)" } else { "" },
+        if thought.starred() { " (starred)" } else { "" },
+        if thought.reviewed() {
+            " (reviewed)"
+        } else {
+            ""
+        },
+        if thought.answered() {
+            " (answered)"
+        } else {
+            ""
+        }
+    )?;
+    writeln!(out, "Captured: {}", thought.created_at())?;
+    if let Some(parent_id) = thought.parent_id() {
+        let parent = get_thought(conn, parent_id)?
+            .map(|p| preview(p.content(), CONTENT_WIDTH))
+            .unwrap_or_default();
+        writeln!(out, "Part of: #{} {}", parent_id, parent)?;
+    }
+    if let Some(progress) = progress(conn, id)? {
+        writeln!(out, "Progress: {}", progress)?;
+    }
+    if !thought.tags().is_empty() {
+        writeln!(out, "Tags: {}", thought.tags().join(", "))?;
+    }
+    if let Some(suggestion) = thought.suggestion() {
+        writeln!(out, "Suggested: {}", suggestion_line(suggestion))?;
+    }
+    if let Some(details) = thought.todo() {
+        let mut line = format!("Status: {}", details.status());
+        if let Some(priority) = details.priority() {
+            line.push_str(&format!(", {} priority", priority));
+        }
+        if let Some(due) = details.due() {
+            line.push_str(&format!(", due {}", due));
+        }
+        if let Some(completed_at) = details.completed_at() {
+            line.push_str(&format!(", completed {}", completed_at));
+        }
+        writeln!(out, "{}", line)?;
+    }
+    if let Some(schedule) = thought.schedule() {
+        writeln!(
+            out,
+            "Next review: {} (every {}, ease {:.2})",
+            schedule.next_review_on(),
+            days(schedule.interval_days()),
+            schedule.ease()
+        )?;
+    }
+    writeln!(out, "\n{}", thought.content())?;
+    if let Some(score) = thought.score() {
+        writeln!(out, "\nScore: {}/10 novelty", score.novelty_score())?;
+        writeln!(out, "Summary: {}", score.summary())?;
+        writeln!(out, "Scale: {}", score.scale())?;
+        writeln!(out, "Effort: {}", score.effort_estimate())?;
+        writeln!(out, "Risks:")?;
+        for risk in score.risks() {
+            writeln!(out, "  - {}", risk)?;
+        }
+        writeln!(out, "Next steps:")?;
+        for (i, step) in score.next_steps().iter().enumerate() {
+            writeln!(out, "  {}. {}", i + 1, step)?;
+        }
+    }
+    if let Some(answer) = thought.answer() {
+        writeln!(out, "\nAnswer:\n{}", answer)?;
+    }
+    if let Some(analysis) = thought.analysis() {
+        writeln!(out, "\nAnalysis:\n{}", analysis)?;
+    }
+    let links = links_from(conn, id)?;
+    let links = links.iter().map(|(kind, t)| (kind.label(), t));
+    print_links(out, "Links", links)?;
+    let backlinks = backlinks(conn, id)?;
+    let backlinks = backlinks.iter().map(|(kind, t)| (kind.backlink_label(), t));
+    print_links(out, "Backlinks", backlinks)?;
+    let tree = descendants(conn, id, ThoughtState::Active)?;
+    if !tree.is_empty() {
+        writeln!(out, "\nSub-thoughts:")?;
+    }
+    for (depth, thought) in &tree {
+        let status = thought
+            .todo()
+            .map(|details| format!(" ({})", details.status()))
+            .unwrap_or_default();
+        writeln!(
+            out,
+            "{}#{} [{}] {}{}",
+            "  ".repeat(*depth),
+            thought.id(),
+            thought.thought_type(),
+            preview(thought.content(), CONTENT_WIDTH),
+            status
+        )?;
+    }
+    Ok(())
+}
+
+fn print_links<'a, W: Write>(
+    out: &mut W,
+    title: &str,
+    links: impl ExactSizeIterator<Item = (&'static str, &'a Thought)>,
+) -> Result<(), AppError> {
+    if links.len() == 0 {
+        return Ok(());
+    }
+    writeln!(out, "\n{}:", title)?;
+    for (label, thought) in links {
+        writeln!(
+            out,
+            "  {} #{} [{}] {}",
+            label,
+            thought.id(),
+            thought.thought_type(),
+            preview(thought.content(), CONTENT_WIDTH)
+        )?;
+    }
+    Ok(())
+}
+
+/// Files a thought under `parent`, refusing to put a thought under itself or
+/// under one of its own sub-thoughts.
+pub fn reparent<W: Write>(
+    conn: &Connection,
+    id: i32,
+    parent: Option<i32>,
+    out: &mut W,
+) -> Result<(), AppError> {
+    for id in std::iter::once(id).chain(parent) {
+        get_thought(conn, id)?.ok_or_else(|| AppError::NotFound(format!("thought #{}", id)))?;
+    }
+    match parent {
+        Some(parent) if parent == id => writeln!(out, "A thought cannot be its own parent")?,
+        Some(parent) if is_descendant(conn, parent, id)? => writeln!(
+            out,
+            "#{} is already under #{}, it cannot also be its parent",
+            parent, id
+        )?,
+        Some(parent) => {
+            set_parent(conn, id, Some(parent))?;
+            writeln!(out, "Filed #{} under #{}", id, parent)?;
+        }
+        None => {
+            set_parent(conn, id, None)?;
+            writeln!(out, "#{} is now top-level", id)?;
+        }
+    }
+    Ok(())
+}
+
+/// Adds todos under a thought in one step: the next steps of its idea score,
+/// or with `ai` or no score, subtasks suggested by the AI provider.
+pub fn break_down<W: Write>(
+    args: &Args,
+    conn: &Connection,
+    id: i32,
+    ai: bool,
+    out: &mut W,
+) -> Result<(), AppError> {
+    let thought =
+        get_thought(conn, id)?.ok_or_else(|| AppError::NotFound(format!("thought #{}", id)))?;
+    let steps = match thought.score() {
+        Some(score) if !ai && !score.next_steps().is_empty() => score.next_steps().to_vec(),
+        _ => {
+            let config = args.config()?;
+            let session = Session::from_config(conn, &config);
+            subtasks::suggest(&session, thought.content())?
+        }
+    };
+    if steps.is_empty() {
+        writeln!(out, "No subtasks for #{}", id)?;
+    }
+    for (child, step) in add_subtasks(conn, id, &steps)?.iter().zip(&steps) {
+        writeln!(out, "Added #{} under #{}: {}", child, id, step)?;
+    }
+    Ok(())
+}
+
+/// Adds a link between two thoughts, or with `remove` takes links away.
+pub fn link<W: Write>(
+    conn: &Connection,
+    source: i32,
+    target: i32,
+    kind: Option<LinkKind>,
+    remove: bool,
+    out: &mut W,
+) -> Result<(), AppError> {
+    if source == target {
+        writeln!(out, "A thought cannot link to itself")?;
+        return Ok(());
+    }
+    for id in [source, target] {
+        get_thought(conn, id)?.ok_or_else(|| AppError::NotFound(format!("thought #{}", id)))?;
+    }
+    if remove {
+        match remove_link(conn, source, target, kind)? {
+            0 => writeln!(out, "No link from #{} to #{}", source, target)?,
+            removed => writeln!(
+                out,
+                "Removed {} link(s) from #{} to #{}",
+                removed, source, target
+            )?,
+        }
+        return Ok(());
+    }
+    let kind = kind.unwrap_or(LinkKind::RelatesTo);
+    let verb = if add_link(conn, source, target, kind)? {
+        "now"
+    } else {
+        "already"
+    };
+    writeln!(out, "#{} {} {} #{}", source, verb, kind.label(), target)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_operations::{save_analysis, save_answer, save_score, setup_db, write_to_db};
+    use crate::scoring::IdeaScore;
+    use crate::writer_config::Args as WriterArgs;
+    use clap::Parser;
+
+    fn run_to_string(command: Command, conn: &Connection) -> Result<String, AppError> {
+        let args = Args::try_parse_from(["thought"]).unwrap();
+        let mut out = Vec::new();
+        run(&command, &args, conn, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn run_args(args: &[&str], conn: &Connection) -> String {
+        let args =
+            Args::try_parse_from(std::iter::once("thought").chain(args.iter().copied())).unwrap();
+        let mut out = Vec::new();
+        run(args.command().unwrap(), &args, conn, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn listed_conn() -> Connection {
+        let conn = setup_db(":memory:").unwrap();
+        for (thought_type, content, tags, created_at) in [
+            (
+                "Project",
+                "Habit tracker",
+                "cli,habits",
+                "2025-01-05 09:00:00",
+            ),
+            ("Todo", "Buy milk", "", "2025-01-10 09:00:00"),
+            (
+                "Project",
+                "Recipe app with a\nlong description",
+                "cli",
+                "2025-02-01 09:00:00",
+            ),
+        ] {
+            conn.execute(
+                "INSERT INTO thoughts (type, content, tags, created_at) VALUES (?, ?, ?, ?)",
+                [thought_type, content, tags, created_at],
+            )
+            .unwrap();
+        }
+        conn.execute("UPDATE thoughts SET reviewed = true WHERE id = 1", [])
+            .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_list_table_filters_and_pages() {
+        let conn = listed_conn();
+
+        let output = run_args(&["list"], &conn);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            "ID  Type     Captured    Reviewed  Tags        Content"
+        );
+        assert_eq!(
+            lines[1],
+            " 3  Project  2025-02-01  no        cli         Recipe app with a long description"
+        );
+        assert_eq!(lines[4], "Page 1 of 1 (3 thoughts)");
+
+        let output = run_args(&["list", "--tag", "cli", "--unreviewed"], &conn);
+        assert!(output.contains("Recipe app"));
+        assert!(!output.contains("Habit tracker"));
+
+        let output = run_args(
+            &["list", "--search", "MILK", "--until", "2025-01-31"],
+            &conn,
+        );
+        assert!(output.contains("Buy milk"));
+        assert!(output.ends_with("(1 thoughts)\n"));
+
+        let output = run_args(
+            &[
+                "list", "--sort", "id", "--asc", "--limit", "2", "--page", "2",
+            ],
+            &conn,
+        );
+        assert!(output.contains("Recipe app"));
+        assert!(output.ends_with("Page 2 of 2 (3 thoughts)\n"));
+
+        let output = run_args(&["list", "--thought-type", "question"], &conn);
+        assert_eq!(output, "No thoughts found\n");
+    }
+
+    #[test]
+    fn test_list_json_formats() {
+        let conn = listed_conn();
+
+        let output = run_args(
+            &["list", "--format", "ndjson", "--since", "2025-01-06"],
+            &conn,
+        );
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["id"], 3);
+        assert_eq!(lines[1]["type"], "Todo");
+        assert!(lines[1].get("analysis").is_none());
+
+        let output = run_args(&["list", "--format", "json"], &conn);
+        let all: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(all.as_array().unwrap().len(), 3);
+        assert_eq!(all[2]["tags"], serde_json::json!(["cli", "habits"]));
+    }
+
+    #[test]
+    fn test_show_prints_thought_and_analysis() {
+        let conn = setup_db(":memory:").unwrap();
+        let args = WriterArgs::new_for_test(ThoughtType::Project, "Habit tracker".to_string())
+            .with_tags(&["cli"]);
+        write_to_db(&conn, &args).unwrap();
+        save_analysis(&conn, 1, "Small weekend project").unwrap();
+
+        let output = run_to_string(Command::Show { id: 1 }, &conn).unwrap();
+
+        assert!(output.starts_with("#1 [Project]\n"));
+        assert!(output.contains("Tags: cli"));
+        assert!(output.contains("\nHabit tracker\n"));
+        assert!(output.contains("Analysis:\nSmall weekend project"));
+    }
+
+    #[test]
+    fn test_show_missing_thought() {
+        let conn = setup_db(":memory:").unwrap();
+
+        let result = run_to_string(Command::Show { id: 7 }, &conn);
+
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_suggestions_accept_and_reject() {
+        let conn = setup_db(":memory:").unwrap();
+        for content in ["Build a habit tracker", "Buy milk"] {
+            let args = WriterArgs::new_for_test(ThoughtType::Misc, content.to_string());
+            write_to_db(&conn, &args).unwrap();
+        }
+        let project = Classification::new(ThoughtType::Project, vec!["habits".to_string()]);
+        save_suggestion(&conn, 1, &project).unwrap();
+        save_suggestion(&conn, 2, &Classification::new(ThoughtType::Todo, vec![])).unwrap();
+
+        let output = run_to_string(Command::Suggestions, &conn).unwrap();
+        assert!(output.contains("#1 [Misc] -> Project (tags: habits)"));
+        assert!(output.contains("#2 [Misc] -> Todo"));
+        let output = run_to_string(Command::Show { id: 1 }, &conn).unwrap();
+        assert!(output.contains("Suggested: Project (tags: habits)"));
+
+        let output = run_to_string(Command::Accept { ids: vec![1, 3] }, &conn).unwrap();
+        assert_eq!(
+            output,
+            "Accepted suggestion for #1\nNo pending suggestion for #3\n"
+        );
+        run_to_string(Command::Reject { ids: vec![2] }, &conn).unwrap();
+
+        let output = run_to_string(Command::Suggestions, &conn).unwrap();
+        assert_eq!(output, "No pending suggestions\n");
+        assert!(
+            run_to_string(Command::Show { id: 1 }, &conn)
+                .unwrap()
+                .starts_with("#1 [Project]")
+        );
+    }
+
+    #[test]
+    fn test_reclassify_with_nothing_to_do_skips_config() {
+        let conn = setup_db(":memory:").unwrap();
+
+        let output = run_to_string(
+            Command::Reclassify {
+                thought_type: ThoughtType::Misc,
+                limit: 10,
+            },
+            &conn,
+        )
+        .unwrap();
+
+        assert_eq!(output, "No Misc thoughts to classify\n");
+    }
+
+    #[test]
+    fn test_questions_and_answered() {
+        let conn = setup_db(":memory:").unwrap();
+        for content in ["How do CRDTs merge?", "What is a B-tree?"] {
+            let args = WriterArgs::new_for_test(ThoughtType::Question, content.to_string());
+            write_to_db(&conn, &args).unwrap();
+        }
+        save_answer(&conn, 1, "By merging states").unwrap();
+
+        let output = run_to_string(Command::Questions, &conn).unwrap();
+        assert!(output.contains("How do CRDTs merge? [has answer]\n"));
+        assert!(output.contains("What is a B-tree?\n"));
+
+        let output = run_to_string(
+            Command::Answered {
+                ids: vec![1],
+                reopen: false,
+            },
+            &conn,
+        )
+        .unwrap();
+        assert_eq!(output, "Marked #1 answered\n");
+        let output = run_to_string(Command::Show { id: 1 }, &conn).unwrap();
+        assert!(output.starts_with("#1 [Question] (answered)\n"));
+        assert!(output.contains("Answer:\nBy merging states"));
+
+        let output = run_to_string(Command::Questions, &conn).unwrap();
+        assert!(!output.contains("CRDTs"));
+    }
+
+    #[test]
+    fn test_edit_and_delete_commands() {
+        let conn = listed_conn();
+
+        let output = run_args(&["edit", "2", "--content", "Buy oat milk"], &conn);
+        assert_eq!(output, "Updated #2\n");
+        let output = run_args(&["edit", "2", "--type", "todo"], &conn);
+        assert_eq!(output, "No changes to #2\n");
+        let output = run_args(&["edit", "2", "--content", " "], &conn);
+        assert_eq!(output, "Content cannot be empty, #2 left unchanged\n");
+        assert!(run_args(&["show", "2"], &conn).contains("\nBuy oat milk\n"));
+    }
+
+    #[test]
+    fn test_unreview_by_id_and_filter() {
+        let conn = listed_conn();
+
+        let output = run_args(&["unreview", "1", "2"], &conn);
+        assert_eq!(
+            output,
+            "Queued #1 for the next digest\n#2 is not a reviewed thought\n"
+        );
+        conn.execute("UPDATE thoughts SET reviewed = true", [])
+            .unwrap();
+        let output = run_args(&["unreview", "--tag", "cli"], &conn);
+        assert_eq!(output, "Queued 2 thoughts for the next digest\n");
+        let output = run_args(&["list", "--reviewed", "--format", "ndjson"], &conn);
+        assert!(output.contains("Buy milk") && output.lines().count() == 1);
+    }
+
+    #[test]
+    fn test_rate_schedules_next_review() {
+        let conn = listed_conn();
+
+        let output = run_args(&["rate", "1", "5"], &conn);
+        assert!(output.starts_with("Rated #1, next review on "));
+        assert!(output.ends_with(" (in 1 day)\n"));
+        run_args(&["rate", "1", "4"], &conn);
+        let output = run_args(&["show", "1"], &conn);
+        assert!(output.contains("(every 6 days, ease 2.60)"));
+        assert!(Args::try_parse_from(["thought", "rate", "1", "6"]).is_err());
+        let result = run_to_string(Command::Rate { id: 9, quality: 3 }, &conn);
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_todo_commands() {
+        let conn = listed_conn();
+
+        let output = run_args(
+            &[
+                "todo",
+                "set",
+                "2",
+                "--due",
+                "2020-01-01",
+                "--priority",
+                "low",
+            ],
+            &conn,
+        );
+        assert_eq!(output, "Updated #2\n");
+        assert_eq!(run_args(&["todo", "set", "2"], &conn), "No changes to #2\n");
+        assert_eq!(
+            run_args(&["todo", "set", "1", "--priority", "high"], &conn),
+            "#1 is not a todo\n"
+        );
+        let output = run_args(&["todo", "list", "--overdue"], &conn);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "ID  Status  Priority  Due                   Content",
+                " 2  open    low       2020-01-01 (overdue)  Buy milk",
+            ]
+        );
+        assert!(
+            run_args(&["show", "2"], &conn)
+                .contains("Status: open, low priority, due 2020-01-01\n")
+        );
+
+        assert_eq!(
+            run_args(&["done", "2", "3"], &conn),
+            "Done #2\n#3 is not a todo\n"
+        );
+        assert_eq!(run_args(&["todo", "list"], &conn), "No todos found\n");
+        let output = run_args(
+            &["todo", "list", "--status", "done", "--format", "ndjson"],
+            &conn,
+        );
+        assert!(output.contains(r#""status":"done""#));
+    }
+
+    #[test]
+    fn test_export_ics() {
+        let conn = listed_conn();
+        run_args(&["todo", "set", "2", "--due", "2025-01-20"], &conn);
+
+        let output = run_args(&["export", "ics", "--events"], &conn);
+        assert!(output.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert_eq!(output.matches("BEGIN:VTODO").count(), 1);
+        assert!(output.contains("\r\nSUMMARY:Buy milk\r\n"));
+        assert!(output.contains("\r\nDTSTART;VALUE=DATE:20250120\r\n"));
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let output = run_args(&["export", "ics", "--output", path], &conn);
+        assert_eq!(output, format!("Exported 1 todos to {}\n", path));
+        assert!(fs::read_to_string(path).unwrap().contains("BEGIN:VTODO"));
+    }
+
+    #[test]
+    fn test_link_command_and_thread_view() {
+        let conn = listed_conn();
+
+        let output = run_args(&["link", "2", "1", "--kind", "derived-from"], &conn);
+        assert_eq!(output, "#2 now derived from #1\n");
+        let output = run_args(&["link", "2", "1", "--kind", "derived-from"], &conn);
+        assert_eq!(output, "#2 already derived from #1\n");
+        assert_eq!(
+            run_args(&["link", "3", "1"], &conn),
+            "#3 now relates to #1\n"
+        );
+        assert_eq!(
+            run_args(&["link", "1", "1"], &conn),
+            "A thought cannot link to itself\n"
+        );
+        let result = run_to_string(
+            Command::Link {
+                source: 1,
+                target: 9,
+                kind: None,
+                remove: false,
+            },
+            &conn,
+        );
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+
+        let output = run_args(&["show", "1"], &conn);
+        assert!(output.ends_with(
+            "\nBacklinks:\n  source of #2 [Todo] Buy milk\n  relates to #3 [Project] Recipe app with a long description\n"
+        ));
+        assert!(
+            run_args(&["show", "2"], &conn)
+                .ends_with("\nLinks:\n  derived from #1 [Project] Habit tracker\n")
+        );
+
+        let output = run_args(&["link", "3", "1", "--remove"], &conn);
+        assert_eq!(output, "Removed 1 link(s) from #3 to #1\n");
+        assert_eq!(
+            run_args(&["link", "3", "1", "--remove"], &conn),
+            "No link from #3 to #1\n"
+        );
+    }
+
+    #[test]
+    fn test_archive_trash_and_restore_commands() {
+        let conn = listed_conn();
+
+        assert_eq!(run_args(&["archive", "1"], &conn), "Archived #1\n");
+        let output = run_args(&["delete", "2", "9"], &conn);
+        assert_eq!(output, "Trashed #2\nNo thought #9\n");
+        assert!(run_args(&["show", "1"], &conn).starts_with("#1 [Project] (archived)"));
+        let output = run_args(&["list", "--format", "ndjson"], &conn);
+        assert_eq!(output.lines().count(), 1);
+        assert!(run_args(&["list", "--state", "trashed"], &conn).contains("Buy milk"));
+
+        assert_eq!(run_args(&["restore", "1"], &conn), "Restored #1\n");
+        let output = run_args(&["empty-trash"], &conn);
+        assert_eq!(output, "Deleted 1 trashed thoughts\n");
+        let result = run_to_string(Command::Show { id: 2 }, &conn);
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+        assert!(run_args(&["show", "1"], &conn).starts_with("#1 [Project] (reviewed)"));
+    }
+
+    #[test]
+    fn test_edit_in_editor_reads_back_file() {
+        let edited = edit_in_editor("sed -i s/Habbit/Habit/", "Habbit tracker\n").unwrap();
+        assert_eq!(edited, "Habit tracker");
+
+        assert!(edit_in_editor("false", "Unchanged").is_err());
+    }
+
+    #[test]
+    fn test_list_matches_tags_and_search_literally() {
+        let conn = setup_db(":memory:").unwrap();
+        for (content, tags) in [
+            ("Parser", &[" cli ", "a_b"][..]),
+            ("Look-alike", &["axb"][..]),
+            ("Done 100%", &[][..]),
+        ] {
+            let args = WriterArgs::new_for_test(ThoughtType::Notes, content.to_string());
+            write_to_db(&conn, &args.with_tags(tags)).unwrap();
+        }
+
+        let output = run_args(&["list", "--tag", "cli"], &conn);
+        assert!(output.contains("Parser") && output.ends_with("(1 thoughts)\n"));
+        let output = run_args(&["list", "--tag", "a_b"], &conn);
+        assert!(output.contains("Parser") && output.ends_with("(1 thoughts)\n"));
+        let output = run_args(&["list", "--search", "0%"], &conn);
+        assert!(output.contains("Done 100%") && output.ends_with("(1 thoughts)\n"));
+        assert_eq!(
+            run_args(&["list", "--search", "_"], &conn),
+            "No thoughts found\n"
+        );
+    }
+
+    #[test]
+    fn test_parent_and_tree_listing() {
+        let conn = listed_conn();
+
+        assert_eq!(
+            run_args(&["parent", "2", "1"], &conn),
+            "Filed #2 under #1\n"
+        );
+        assert_eq!(
+            run_args(&["parent", "1", "2"], &conn),
+            "#2 is already under #1, it cannot also be its parent\n"
+        );
+        assert_eq!(
+            run_args(&["parent", "1", "1"], &conn),
+            "A thought cannot be its own parent\n"
+        );
+        let result = run_to_string(
+            Command::Parent {
+                id: 2,
+                parent: Some(9),
+            },
+            &conn,
+        );
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+
+        let output = run_args(&["list", "--tree", "--sort", "id", "--asc"], &conn);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[1..4],
+            [
+                " 1  Project  2025-01-05  yes       cli,habits  Habit tracker [0/1 done (0%)]",
+                " 2  Todo     2025-01-10  no                      Buy milk",
+                " 3  Project  2025-02-01  no        cli         Recipe app with a long description",
+            ]
+        );
+        assert_eq!(lines[4], "Page 1 of 1 (2 thoughts)");
+
+        run_args(&["done", "2"], &conn);
+        let output = run_args(&["show", "1"], &conn);
+        assert!(output.contains("\nProgress: 1/1 done (100%)\n"));
+        assert!(output.ends_with("\nSub-thoughts:\n  #2 [Todo] Buy milk (done)\n"));
+        assert!(run_args(&["show", "2"], &conn).contains("\nPart of: #1 Habit tracker\n"));
+
+        assert_eq!(run_args(&["parent", "2"], &conn), "#2 is now top-level\n");
+        assert!(!run_args(&["show", "1"], &conn).contains("Sub-thoughts"));
+    }
+
+    #[test]
+    fn test_tree_listing_keeps_sub_thoughts_of_archived_parent() {
+        let conn = listed_conn();
+        run_args(&["parent", "2", "1"], &conn);
+        run_args(&["archive", "1"], &conn);
+
+        let output = run_args(&["list", "--tree", "--sort", "id", "--asc"], &conn);
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[1].starts_with(" 2  Todo"));
+        assert!(lines[2].starts_with(" 3  Project"));
+        assert_eq!(lines[3], "Page 1 of 1 (2 thoughts)");
+
+        let output = run_args(&["list", "--tree", "--state", "archived"], &conn);
+        assert!(output.contains("Habit tracker"));
+        assert!(!output.contains("Buy milk"));
+    }
+
+    #[test]
+    fn test_subtasks_from_idea_score_next_steps() {
+        let conn = listed_conn();
+        let score = IdeaScore::new(
+            String::new(),
+            "hobby".to_string(),
+            String::new(),
+            6,
+            vec![],
+            vec!["Sketch the schema".to_string(), "Build the CLI".to_string()],
+        );
+        save_score(&conn, 1, &score).unwrap();
+
+        let output = run_args(&["subtasks", "1"], &conn);
+
+        assert_eq!(
+            output,
+            "Added #4 under #1: Sketch the schema\nAdded #5 under #1: Build the CLI\n"
+        );
+        let output = run_args(&["list", "--tree", "--thought-type", "project"], &conn);
+        assert!(output.contains("Habit tracker [0/2 done (0%)]"));
+    }
+
+    #[test]
+    fn test_pin_and_star_toggle() {
+        let conn = listed_conn();
+
+        assert_eq!(
+            run_args(&["pin", "1", "9"], &conn),
+            "Pinned #1\nNo thought #9\n"
+        );
+        assert_eq!(run_args(&["star", "3"], &conn), "Starred #3\n");
+        assert!(run_args(&["show", "1"], &conn).starts_with("#1 [Project] (pinned) (reviewed)\n"));
+        let output = run_args(&["list", "--starred", "--format", "ndjson"], &conn);
+        let starred: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(
+            (starred["id"].as_i64(), starred["starred"].as_bool()),
+            (Some(3), Some(true))
+        );
+        assert!(run_args(&["list", "--pinned"], &conn).contains("Habit tracker"));
+        let output = run_args(&["list", "--sort", "id", "--asc"], &conn);
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[1].starts_with(" 1  Project    2025-01-05"));
+        assert!(lines[3].starts_with(" 3  Project ★  2025-02-01"));
+
+        assert_eq!(run_args(&["pin", "1"], &conn), "Unpinned #1\n");
+        assert_eq!(
+            run_args(&["list", "--pinned"], &conn),
+            "No thoughts found\n"
+        );
+    }
+}
@@ -1,18 +1,198 @@
+use crate::classify::Classification;
 use crate::errors::AppError;
-use crate::thought::Thought;
-use crate::writer_config::Args;
-use rusqlite::{Connection, Result as SqlResult};
+use crate::links::{LinkKind, parse_references};
+use crate::reader_config::{ResurfaceMode, SortBy, ThoughtFilter};
+use crate::review::ReviewSchedule;
+use crate::scoring::IdeaScore;
+use crate::thought::{Thought, ThoughtState};
+use crate::todo::{Priority, Progress, TodoDetails, TodoStatus};
+use crate::usage::{TokenUsage, UsageTotal};
+use crate::writer_config::{Args, ThoughtType};
+use rusqlite::types::Value;
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult, Row, params, params_from_iter};
+use std::collections::HashMap;
+
+const SELECT_THOUGHTS: &str =
+    "SELECT t.id, t.type, t.content, t.reviewed, t.tags, t.created_at, a.content,
+        s.summary, s.scale, s.effort_estimate, s.novelty_score, s.risks, s.next_steps,
+        t.suggested_type, t.suggested_tags, t.suggestion_status, t.answered, ans.content,
+        t.state, t.repetitions, t.review_interval, t.ease, t.next_review_at,
+        t.status, t.due, t.priority, t.completed_at, t.parent_id, t.pinned, t.starred
+    FROM thoughts t
+    LEFT JOIN analyses a ON a.thought_id = t.id
+    LEFT JOIN idea_scores s ON s.thought_id = t.id
+    LEFT JOIN answers ans ON ans.thought_id = t.id";
+
+/// Columns added to `thoughts` after the original schema. Applied in order on
+/// every start so existing databases pick them up.
+const THOUGHT_MIGRATIONS: &[(&str, &str)] = &[
+    ("tags", "TEXT NOT NULL DEFAULT ''"),
+    ("created_at", "TEXT"),
+    ("suggested_type", "TEXT"),
+    ("suggested_tags", "TEXT"),
+    ("suggestion_status", "TEXT"),
+    ("original_type", "TEXT"),
+    ("answered", "BOOLEAN NOT NULL DEFAULT FALSE"),
+    ("state", "TEXT NOT NULL DEFAULT 'active'"),
+    ("resurfaced_at", "TEXT"),
+    ("resurface_count", "INTEGER NOT NULL DEFAULT 0"),
+    ("repetitions", "INTEGER NOT NULL DEFAULT 0"),
+    ("review_interval", "INTEGER NOT NULL DEFAULT 0"),
+    ("ease", "REAL NOT NULL DEFAULT 2.5"),
+    ("next_review_at", "TEXT"),
+    ("status", "TEXT NOT NULL DEFAULT 'open'"),
+    ("due", "TEXT"),
+    ("priority", "TEXT"),
+    ("completed_at", "TEXT"),
+    ("parent_id", "INTEGER"),
+    ("pinned", "BOOLEAN NOT NULL DEFAULT FALSE"),
+    ("starred", "BOOLEAN NOT NULL DEFAULT FALSE"),
+];
+
+const SUGGESTION_PENDING: &str = "pending";
+const SUGGESTION_ACCEPTED: &str = "accepted";
+const SUGGESTION_REJECTED: &str = "rejected";
+
+const REVISION_EDIT: &str = "edit";
+const REVISION_DELETE: &str = "delete";
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), AppError> {
+    let exists = conn
+        .prepare(&format!(
+            "SELECT 1 FROM pragma_table_info('{}') WHERE name = ?",
+            table
+        ))?
+        .exists([column])?;
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+fn migrate(conn: &Connection) -> Result<(), AppError> {
+    THOUGHT_MIGRATIONS
+        .iter()
+        .try_for_each(|(column, definition)| {
+            add_column_if_missing(conn, "thoughts", column, definition)
+        })?;
+    conn.execute(
+        "UPDATE thoughts SET created_at = datetime('now') WHERE created_at IS NULL",
+        [],
+    )?;
+    Ok(())
+}
+
+fn split_tags(tags: String) -> Vec<String> {
+    tags.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn list_from_json(value: String) -> Vec<String> {
+    serde_json::from_str(&value).unwrap_or_default()
+}
+
+fn score_from_row(row: &Row) -> SqlResult<Option<IdeaScore>> {
+    let novelty_score: Option<u8> = row.get(10)?;
+    let Some(novelty_score) = novelty_score else {
+        return Ok(None);
+    };
+    Ok(Some(IdeaScore::new(
+        row.get(7)?,
+        row.get(8)?,
+        row.get(9)?,
+        novelty_score,
+        list_from_json(row.get(11)?),
+        list_from_json(row.get(12)?),
+    )))
+}
+
+/// The AI suggested classification, only while it awaits a decision.
+fn suggestion_from_row(row: &Row) -> SqlResult<Option<Classification>> {
+    let status: Option<String> = row.get(15)?;
+    let suggested_type: Option<ThoughtType> = row.get(13)?;
+    match (status.as_deref(), suggested_type) {
+        (Some(SUGGESTION_PENDING), Some(thought_type)) => {
+            let tags: Option<String> = row.get(14)?;
+            Ok(Some(Classification::new(
+                thought_type,
+                split_tags(tags.unwrap_or_default()),
+            )))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The SM-2 schedule, only once the thought has been rated.
+fn schedule_from_row(row: &Row) -> SqlResult<Option<ReviewSchedule>> {
+    let next_review_at: Option<String> = row.get(22)?;
+    next_review_at
+        .map(|next| {
+            Ok(ReviewSchedule::new(
+                row.get(19)?,
+                row.get(20)?,
+                row.get(21)?,
+                next,
+            ))
+        })
+        .transpose()
+}
+
+/// Status, due date and priority, only for todos.
+fn todo_from_row(row: &Row, thought_type: &ThoughtType) -> SqlResult<Option<TodoDetails>> {
+    if !matches!(thought_type, ThoughtType::Todo) {
+        return Ok(None);
+    }
+    Ok(Some(TodoDetails::new(
+        row.get(23)?,
+        row.get(24)?,
+        row.get(25)?,
+        row.get(26)?,
+    )))
+}
+
+fn thought_from_row(row: &Row) -> SqlResult<Thought> {
+    let id = row.get(0)?;
+    let thought_type: ThoughtType = row.get(1)?;
+    let todo = todo_from_row(row, &thought_type)?;
+    let content = row.get(2)?;
+    let reviewed = row.get(3)?;
+    let tags: String = row.get(4)?;
+    let created_at: Option<String> = row.get(5)?;
+    let analysis = row.get(6)?;
+    Ok(Thought::new(id, thought_type, content, reviewed)
+        .with_tags(split_tags(tags))
+        .with_created_at(created_at.unwrap_or_default())
+        .with_analysis(analysis)
+        .with_score(score_from_row(row)?)
+        .with_suggestion(suggestion_from_row(row)?)
+        .with_answered(row.get(16)?)
+        .with_answer(row.get(17)?)
+        .with_state(row.get(18)?)
+        .with_schedule(schedule_from_row(row)?)
+        .with_todo(todo)
+        .with_parent(row.get(27)?)
+        .with_pinned(row.get(28)?)
+        .with_starred(row.get(29)?))
+}
 
 fn read_from_db(conn: &Connection) -> Result<Vec<Thought>, AppError> {
     let thoughts: Vec<Thought> = conn
-        .prepare("SELECT id, type, content, reviewed FROM thoughts WHERE reviewed = false")?
-        .query_map([], |row| {
-            let id = row.get(0)?;
-            let thought_type = row.get(1)?;
-            let content = row.get(2)?;
-            let reviewed = row.get(3)?;
-            Ok(Thought::new(id, thought_type, content, reviewed))
-        })?
+        .prepare(&format!(
+            "{} WHERE t.reviewed = false AND t.state = ?",
+            SELECT_THOUGHTS
+        ))?
+        .query_map([ThoughtState::Active.to_string()], thought_from_row)?
         .collect::<SqlResult<Vec<Thought>>>()?;
     thoughts.iter().for_each(|t| {
         let _ = conn.execute("UPDATE thoughts SET reviewed = true WHERE id = ?", [t.id()]);
@@ -40,6 +220,87 @@ pub fn setup_db(db_name: &str) -> SqlResult<Connection, AppError> {
         )",
         [],
     )?;
+    migrate(&conn)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS analyses (
+            thought_id INTEGER PRIMARY KEY REFERENCES thoughts(id),
+            content TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS idea_scores (
+            thought_id INTEGER PRIMARY KEY REFERENCES thoughts(id),
+            summary TEXT NOT NULL,
+            scale TEXT NOT NULL,
+            effort_estimate TEXT NOT NULL,
+            novelty_score INTEGER NOT NULL,
+            risks TEXT NOT NULL,
+            next_steps TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS answers (
+            thought_id INTEGER PRIMARY KEY REFERENCES thoughts(id),
+            content TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS thought_revisions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            thought_id INTEGER NOT NULL,
+            type TEXT NOT NULL,
+            content TEXT NOT NULL,
+            tags TEXT NOT NULL,
+            action TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS thought_links (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            source_id INTEGER NOT NULL,
+            target_id INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (source_id, target_id, kind)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ai_usage (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            input_tokens INTEGER NOT NULL,
+            output_tokens INTEGER NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS response_cache (
+            key TEXT PRIMARY KEY,
+            response TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS embeddings (
+            thought_id INTEGER PRIMARY KEY REFERENCES thoughts(id),
+            model TEXT NOT NULL,
+            vector BLOB NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
     Ok(conn)
 }
 
@@ -48,18 +309,974 @@ pub fn read(conn: &Connection) -> Result<Vec<Thought>, AppError> {
     update_db(conn, &thoughts)?;
     Ok(thoughts)
 }
-pub fn write_to_db(conn: &Connection, args: &Args) -> SqlResult<(), AppError> {
+/// Fetches a single thought, including any stored analysis.
+pub fn get_thought(conn: &Connection, id: i32) -> Result<Option<Thought>, AppError> {
+    let thought = conn
+        .prepare(&format!("{} WHERE t.id = ?", SELECT_THOUGHTS))?
+        .query_map([id], thought_from_row)?
+        .next()
+        .transpose()?;
+    Ok(thought)
+}
+
+/// Escapes `%`, `_` and the escape character itself so a `LIKE ... ESCAPE '\'`
+/// pattern matches `value` literally.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// The WHERE clause selecting thoughts that match `filter`, with its
+/// parameters.
+fn filter_clause(filter: &ThoughtFilter) -> (String, Vec<Value>) {
+    let mut conditions = vec!["t.state = ?".to_string()];
+    let mut values = vec![Value::Text(filter.state().to_string())];
+    if let Some(thought_type) = filter.thought_type() {
+        conditions.push("t.type = ?".to_string());
+        values.push(Value::Text(thought_type.to_string()));
+    }
+    if let Some(reviewed) = filter.reviewed() {
+        conditions.push("t.reviewed = ?".to_string());
+        values.push(Value::Integer(reviewed.into()));
+    }
+    if let Some(tag) = filter.tag() {
+        conditions.push("(',' || t.tags || ',') LIKE ('%,' || ? || ',%') ESCAPE '\\'".to_string());
+        values.push(Value::Text(escape_like(tag.trim())));
+    }
+    if let Some(since) = filter.since() {
+        conditions.push("date(t.created_at) >= ?".to_string());
+        values.push(Value::Text(since.to_string()));
+    }
+    if let Some(until) = filter.until() {
+        conditions.push("date(t.created_at) <= ?".to_string());
+        values.push(Value::Text(until.to_string()));
+    }
+    if let Some(search) = filter.search() {
+        conditions.push("t.content LIKE ('%' || ? || '%') ESCAPE '\\'".to_string());
+        values.push(Value::Text(escape_like(search)));
+    }
+    if filter.tree() {
+        conditions.push(
+            "(t.parent_id IS NULL OR NOT EXISTS
+                (SELECT 1 FROM thoughts p WHERE p.id = t.parent_id AND p.state = ?))"
+                .to_string(),
+        );
+        values.push(Value::Text(filter.state().to_string()));
+    }
+    if filter.pinned() {
+        conditions.push("t.pinned".to_string());
+    }
+    if filter.starred() {
+        conditions.push("t.starred".to_string());
+    }
+    (format!("WHERE {}", conditions.join(" AND ")), values)
+}
+
+/// One page of the thoughts matching `filter`, in its sort order.
+pub fn list_thoughts(conn: &Connection, filter: &ThoughtFilter) -> Result<Vec<Thought>, AppError> {
+    let (clause, mut values) = filter_clause(filter);
+    let direction = if filter.asc() { "ASC" } else { "DESC" };
+    let order = match filter.sort() {
+        SortBy::Date => format!("t.created_at {0}, t.id {0}", direction),
+        SortBy::Id => format!("t.id {}", direction),
+    };
+    values.push(Value::Integer(filter.limit().into()));
+    values.push(Value::Integer(
+        (i64::from(filter.page()) - 1) * i64::from(filter.limit()),
+    ));
+    let thoughts = conn
+        .prepare(&format!(
+            "{} {} ORDER BY {} LIMIT ? OFFSET ?",
+            SELECT_THOUGHTS, clause, order
+        ))?
+        .query_map(params_from_iter(values), thought_from_row)?
+        .collect::<SqlResult<Vec<Thought>>>()?;
+    Ok(thoughts)
+}
+
+/// Puts a reviewed thought back into the next digest. Returns false when
+/// the thought does not exist or was not reviewed.
+pub fn unreview(conn: &Connection, thought_id: i32) -> Result<bool, AppError> {
+    let updated = conn.execute(
+        "UPDATE thoughts SET reviewed = false WHERE id = ? AND reviewed = true",
+        [thought_id],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Puts every reviewed thought matching `filter`, on any page, back into the
+/// next digest. Returns how many were changed.
+pub fn unreview_matching(conn: &Connection, filter: &ThoughtFilter) -> Result<usize, AppError> {
+    let (clause, values) = filter_clause(filter);
+    let updated = conn.execute(
+        &format!(
+            "UPDATE thoughts SET reviewed = false WHERE id IN
+                (SELECT t.id FROM thoughts t {} AND t.reviewed = true)",
+            clause
+        ),
+        params_from_iter(values),
+    )?;
+    Ok(updated)
+}
+
+/// Up to `count` active, reviewed thoughts captured at least `min_age_days`
+/// ago to show again. Thoughts on a review schedule come back through
+/// [`due_for_review`] instead. Spaced mode only picks thoughts whose interval,
+/// `min_age_days` doubled for every earlier resurfacing, has passed since
+/// they were last shown, longest waiting first.
+pub fn resurface_candidates(
+    conn: &Connection,
+    mode: ResurfaceMode,
+    min_age_days: u32,
+    count: u32,
+) -> Result<Vec<Thought>, AppError> {
+    let (condition, order) = match mode {
+        ResurfaceMode::Random => ("", "RANDOM()"),
+        ResurfaceMode::Spaced => (
+            "AND COALESCE(t.resurfaced_at, t.created_at)
+                <= datetime('now', '-' || (?1 << min(t.resurface_count, 10)) || ' days')",
+            "COALESCE(t.resurfaced_at, t.created_at)",
+        ),
+    };
+    let thoughts = conn
+        .prepare(&format!(
+            "{} WHERE t.reviewed = true AND t.state = ?2 AND t.next_review_at IS NULL
+                AND t.created_at <= datetime('now', '-' || ?1 || ' days') {}
+                ORDER BY {} LIMIT ?3",
+            SELECT_THOUGHTS, condition, order
+        ))?
+        .query_map(
+            params![min_age_days, ThoughtState::Active.to_string(), count],
+            thought_from_row,
+        )?
+        .collect::<SqlResult<Vec<Thought>>>()?;
+    Ok(thoughts)
+}
+
+/// Applies a review rated `quality` from 0 to 5 to a thought's SM-2 schedule
+/// and sets its next review date. Returns false when the thought does not
+/// exist.
+pub fn rate_thought(conn: &Connection, thought_id: i32, quality: u8) -> Result<bool, AppError> {
+    let Some(thought) = get_thought(conn, thought_id)? else {
+        return Ok(false);
+    };
+    let next = thought
+        .schedule()
+        .cloned()
+        .unwrap_or_default()
+        .next(quality);
     conn.execute(
-        "INSERT INTO thoughts (type, content) VALUES (?, ?)",
-        [args.thought_type().to_string(), args.content().to_string()],
+        "UPDATE thoughts SET repetitions = ?, review_interval = ?, ease = ?,
+            next_review_at = datetime('now', '+' || ? || ' days') WHERE id = ?",
+        params![
+            next.repetitions(),
+            next.interval_days(),
+            next.ease(),
+            next.interval_days(),
+            thought_id
+        ],
     )?;
+    Ok(true)
+}
+
+/// Active thoughts whose next review date has come, most overdue first.
+pub fn due_for_review(conn: &Connection) -> Result<Vec<Thought>, AppError> {
+    let thoughts = conn
+        .prepare(&format!(
+            "{} WHERE t.next_review_at <= datetime('now') AND t.state = ?
+                ORDER BY t.next_review_at",
+            SELECT_THOUGHTS
+        ))?
+        .query_map([ThoughtState::Active.to_string()], thought_from_row)?
+        .collect::<SqlResult<Vec<Thought>>>()?;
+    Ok(thoughts)
+}
+
+/// Changes the given fields of a todo; `clear_due` removes its due date.
+/// Marking it done records when, and moving it out of done clears that
+/// again. Returns false when there is no todo with that id.
+pub fn update_todo(
+    conn: &Connection,
+    thought_id: i32,
+    status: Option<TodoStatus>,
+    due: Option<&str>,
+    clear_due: bool,
+    priority: Option<Priority>,
+) -> Result<bool, AppError> {
+    let updated = conn.execute(
+        "UPDATE thoughts SET status = COALESCE(?1, status),
+            due = CASE WHEN ?2 THEN NULL ELSE COALESCE(?3, due) END,
+            priority = COALESCE(?4, priority),
+            completed_at = CASE
+                WHEN COALESCE(?1, status) != ?5 THEN NULL
+                ELSE COALESCE(completed_at, CURRENT_TIMESTAMP) END
+            WHERE id = ?6 AND type = ?7",
+        params![
+            status.map(|s| s.to_string()),
+            clear_due,
+            due,
+            priority.map(|p| p.to_string()),
+            TodoStatus::Done.to_string(),
+            thought_id,
+            ThoughtType::Todo.to_string()
+        ],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Active todos with `status`, or every unfinished one when None, due on or
+/// before `due_by` if given. Soonest due first, undated last, then highest
+/// priority.
+pub fn list_todos(
+    conn: &Connection,
+    status: Option<TodoStatus>,
+    due_by: Option<&str>,
+) -> Result<Vec<Thought>, AppError> {
+    let mut conditions = vec!["t.type = ?", "t.state = ?"];
+    let mut values = vec![
+        Value::Text(ThoughtType::Todo.to_string()),
+        Value::Text(ThoughtState::Active.to_string()),
+    ];
+    match status {
+        Some(status) => {
+            conditions.push("t.status = ?");
+            values.push(Value::Text(status.to_string()));
+        }
+        None => {
+            conditions.push("t.status != ?");
+            values.push(Value::Text(TodoStatus::Done.to_string()));
+        }
+    }
+    if let Some(due_by) = due_by {
+        conditions.push("t.due <= ?");
+        values.push(Value::Text(due_by.to_string()));
+    }
+    let thoughts = conn
+        .prepare(&format!(
+            "{} WHERE {} ORDER BY t.due IS NULL, t.due,
+                CASE t.priority WHEN 'high' THEN 0 WHEN 'medium' THEN 1 WHEN 'low' THEN 2
+                    ELSE 3 END,
+                t.id",
+            SELECT_THOUGHTS,
+            conditions.join(" AND ")
+        ))?
+        .query_map(params_from_iter(values), thought_from_row)?
+        .collect::<SqlResult<Vec<Thought>>>()?;
+    Ok(thoughts)
+}
+
+/// Records that thoughts were shown again, pushing back their next spaced
+/// resurfacing.
+pub fn mark_resurfaced(conn: &Connection, thoughts: &[Thought]) -> Result<(), AppError> {
+    thoughts.iter().try_for_each(|thought| {
+        conn.execute(
+            "UPDATE thoughts SET resurfaced_at = CURRENT_TIMESTAMP,
+                resurface_count = resurface_count + 1 WHERE id = ?",
+            [thought.id()],
+        )?;
+        Ok(())
+    })
+}
+
+/// Number of thoughts matching `filter` across all pages.
+pub fn count_thoughts(conn: &Connection, filter: &ThoughtFilter) -> Result<u32, AppError> {
+    let (clause, values) = filter_clause(filter);
+    let count = conn.query_row(
+        &format!("SELECT COUNT(*) FROM thoughts t {}", clause),
+        params_from_iter(values),
+        |row| row.get(0),
+    )?;
+    Ok(count)
+}
+
+/// Copies the current version of a thought into `thought_revisions`.
+/// Returns false when the thought does not exist.
+fn save_revision(conn: &Connection, thought_id: i32, action: &str) -> Result<bool, AppError> {
+    let saved = conn.execute(
+        "INSERT INTO thought_revisions (thought_id, type, content, tags, action)
+            SELECT id, type, content, tags, ? FROM thoughts WHERE id = ?",
+        params![action, thought_id],
+    )?;
+    Ok(saved > 0)
+}
+
+/// Changes the type and/or content of a thought, keeping the previous
+/// version as a revision. A new content drops the stale embedding. Returns
+/// false when the thought does not exist.
+pub fn edit_thought(
+    conn: &Connection,
+    thought_id: i32,
+    thought_type: Option<&ThoughtType>,
+    content: Option<&str>,
+) -> Result<bool, AppError> {
+    let tx = conn.unchecked_transaction()?;
+    if !save_revision(&tx, thought_id, REVISION_EDIT)? {
+        return Ok(false);
+    }
+    tx.execute(
+        "UPDATE thoughts SET type = COALESCE(?, type), content = COALESCE(?, content)
+            WHERE id = ?",
+        params![
+            thought_type.map(ThoughtType::to_string),
+            content,
+            thought_id
+        ],
+    )?;
+    if let Some(content) = content {
+        tx.execute("DELETE FROM embeddings WHERE thought_id = ?", [thought_id])?;
+        sync_references(&tx, thought_id, content)?;
+    }
+    tx.commit()?;
+    Ok(true)
+}
+
+/// Deletes a thought and everything stored for it. Its last version stays in
+/// `thought_revisions`. Returns false when the thought does not exist.
+pub fn delete_thought(conn: &Connection, thought_id: i32) -> Result<bool, AppError> {
+    let tx = conn.unchecked_transaction()?;
+    if !save_revision(&tx, thought_id, REVISION_DELETE)? {
+        return Ok(false);
+    }
+    for table in ["analyses", "idea_scores", "answers", "embeddings"] {
+        tx.execute(
+            &format!("DELETE FROM {} WHERE thought_id = ?", table),
+            [thought_id],
+        )?;
+    }
+    tx.execute(
+        "DELETE FROM thought_links WHERE source_id = ?1 OR target_id = ?1",
+        [thought_id],
+    )?;
+    // Sub-thoughts move up to the deleted thought's own parent.
+    tx.execute(
+        "UPDATE thoughts SET parent_id = (SELECT parent_id FROM thoughts WHERE id = ?1)
+            WHERE parent_id = ?1",
+        [thought_id],
+    )?;
+    tx.execute("DELETE FROM thoughts WHERE id = ?", [thought_id])?;
+    tx.commit()?;
+    Ok(true)
+}
+
+/// Links `source_id` to `target_id`. Returns false when the link already
+/// exists.
+pub fn add_link(
+    conn: &Connection,
+    source_id: i32,
+    target_id: i32,
+    kind: LinkKind,
+) -> Result<bool, AppError> {
+    let added = conn.execute(
+        "INSERT OR IGNORE INTO thought_links (source_id, target_id, kind) VALUES (?, ?, ?)",
+        params![source_id, target_id, kind.to_string()],
+    )?;
+    Ok(added > 0)
+}
+
+/// Removes the links from `source_id` to `target_id`, only those of `kind`
+/// if given. Returns how many were removed.
+pub fn remove_link(
+    conn: &Connection,
+    source_id: i32,
+    target_id: i32,
+    kind: Option<LinkKind>,
+) -> Result<usize, AppError> {
+    let removed = conn.execute(
+        "DELETE FROM thought_links WHERE source_id = ? AND target_id = ?
+            AND kind = COALESCE(?, kind)",
+        params![source_id, target_id, kind.map(|k| k.to_string())],
+    )?;
+    Ok(removed)
+}
+
+/// Replaces a thought's `references` links with the existing thoughts
+/// referenced as `[[#id]]` in its content.
+fn sync_references(conn: &Connection, thought_id: i32, content: &str) -> Result<(), AppError> {
+    let kind = LinkKind::References.to_string();
+    conn.execute(
+        "DELETE FROM thought_links WHERE source_id = ? AND kind = ?",
+        params![thought_id, kind],
+    )?;
+    for target_id in parse_references(content) {
+        conn.execute(
+            "INSERT OR IGNORE INTO thought_links (source_id, target_id, kind)
+                SELECT ?1, id, ?2 FROM thoughts WHERE id = ?3 AND id != ?1",
+            params![thought_id, kind, target_id],
+        )?;
+    }
     Ok(())
 }
 
+/// Links of thought `thought_id` in one direction, with the thought at the
+/// other end, oldest link first. Trashed thoughts are left out.
+fn linked(
+    conn: &Connection,
+    thought_id: i32,
+    backlinks: bool,
+) -> Result<Vec<(LinkKind, Thought)>, AppError> {
+    let (from, to) = if backlinks {
+        ("target_id", "source_id")
+    } else {
+        ("source_id", "target_id")
+    };
+    let links = conn
+        .prepare(&format!(
+            "SELECT l.kind, l.{to} FROM thought_links l JOIN thoughts t ON t.id = l.{to}
+                WHERE l.{from} = ? AND t.state != ? ORDER BY l.id"
+        ))?
+        .query_map(
+            params![thought_id, ThoughtState::Trashed.to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?
+        .collect::<SqlResult<Vec<(LinkKind, i32)>>>()?;
+    let mut thoughts = Vec::new();
+    for (kind, other) in links {
+        if let Some(thought) = get_thought(conn, other)? {
+            thoughts.push((kind, thought));
+        }
+    }
+    Ok(thoughts)
+}
+
+/// The thoughts `thought_id` links to, with how.
+pub fn links_from(
+    conn: &Connection,
+    thought_id: i32,
+) -> Result<Vec<(LinkKind, Thought)>, AppError> {
+    linked(conn, thought_id, false)
+}
+
+/// The thoughts linking to `thought_id`, with how.
+pub fn backlinks(conn: &Connection, thought_id: i32) -> Result<Vec<(LinkKind, Thought)>, AppError> {
+    linked(conn, thought_id, true)
+}
+
+/// The thoughts in `state` directly under `parent_id`, oldest first.
+fn children(
+    conn: &Connection,
+    parent_id: i32,
+    state: ThoughtState,
+) -> Result<Vec<Thought>, AppError> {
+    let thoughts = conn
+        .prepare(&format!(
+            "{} WHERE t.parent_id = ? AND t.state = ? ORDER BY t.id",
+            SELECT_THOUGHTS
+        ))?
+        .query_map(params![parent_id, state.to_string()], thought_from_row)?
+        .collect::<SqlResult<Vec<Thought>>>()?;
+    Ok(thoughts)
+}
+
+/// The thoughts in `state` under `thought_id` at every level, depth first,
+/// each with its depth below it starting at 1.
+pub fn descendants(
+    conn: &Connection,
+    thought_id: i32,
+    state: ThoughtState,
+) -> Result<Vec<(usize, Thought)>, AppError> {
+    let mut tree = Vec::new();
+    let mut stack: Vec<(usize, Thought)> = children(conn, thought_id, state)?
+        .into_iter()
+        .rev()
+        .map(|t| (1, t))
+        .collect();
+    while let Some((depth, thought)) = stack.pop() {
+        let below = children(conn, *thought.id(), state)?;
+        stack.extend(below.into_iter().rev().map(|t| (depth + 1, t)));
+        tree.push((depth, thought));
+    }
+    Ok(tree)
+}
+
+/// Whether `thought_id` sits anywhere under `ancestor_id`.
+pub fn is_descendant(
+    conn: &Connection,
+    thought_id: i32,
+    ancestor_id: i32,
+) -> Result<bool, AppError> {
+    Ok(conn.query_row(
+        "WITH RECURSIVE below(id) AS (
+            SELECT id FROM thoughts WHERE parent_id = ?1
+            UNION SELECT t.id FROM thoughts t JOIN below b ON t.parent_id = b.id)
+        SELECT EXISTS (SELECT 1 FROM below WHERE id = ?2)",
+        params![ancestor_id, thought_id],
+        |row| row.get(0),
+    )?)
+}
+
+/// Files a thought under `parent_id`, or makes it top-level again with
+/// None. Callers check for cycles. Returns false when the thought does not
+/// exist.
+pub fn set_parent(
+    conn: &Connection,
+    thought_id: i32,
+    parent_id: Option<i32>,
+) -> Result<bool, AppError> {
+    let updated = conn.execute(
+        "UPDATE thoughts SET parent_id = ? WHERE id = ?",
+        params![parent_id, thought_id],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Done and total todos among the active thoughts at every level under
+/// `thought_id`, or None when there are no todos under it.
+pub fn progress(conn: &Connection, thought_id: i32) -> Result<Option<Progress>, AppError> {
+    Ok(progress_of(conn, &[thought_id])?.remove(&thought_id))
+}
+
+/// [`progress`] of each of `thought_ids` in one query, keyed by id. Thoughts
+/// without todos under them are left out.
+pub fn progress_of(
+    conn: &Connection,
+    thought_ids: &[i32],
+) -> Result<HashMap<i32, Progress>, AppError> {
+    if thought_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let placeholders = vec!["?"; thought_ids.len()].join(", ");
+    let mut values: Vec<Value> = thought_ids
+        .iter()
+        .map(|id| Value::Integer((*id).into()))
+        .collect();
+    values.extend([
+        Value::Text(TodoStatus::Done.to_string()),
+        Value::Text(ThoughtType::Todo.to_string()),
+        Value::Text(ThoughtState::Active.to_string()),
+    ]);
+    let progress = conn
+        .prepare(&format!(
+            "WITH RECURSIVE below(root, id) AS (
+                SELECT parent_id, id FROM thoughts WHERE parent_id IN ({})
+                UNION SELECT b.root, t.id FROM thoughts t JOIN below b ON t.parent_id = b.id)
+            SELECT b.root, SUM(t.status = ?), COUNT(*)
+                FROM thoughts t JOIN below b ON b.id = t.id
+                WHERE t.type = ? AND t.state = ?
+                GROUP BY b.root",
+            placeholders
+        ))?
+        .query_map(params_from_iter(values), |row| {
+            Ok((row.get(0)?, Progress::new(row.get(1)?, row.get(2)?)))
+        })?
+        .collect::<SqlResult<HashMap<i32, Progress>>>()?;
+    Ok(progress)
+}
+
+/// Adds each of `contents` as a todo under `parent_id`, all or none, and
+/// returns the new ids in order.
+pub fn add_subtasks(
+    conn: &Connection,
+    parent_id: i32,
+    contents: &[String],
+) -> Result<Vec<i32>, AppError> {
+    let tx = conn.unchecked_transaction()?;
+    let mut ids = Vec::new();
+    for content in contents {
+        tx.execute(
+            "INSERT INTO thoughts (type, content, parent_id, created_at)
+                VALUES (?, ?, ?, datetime('now'))",
+            params![ThoughtType::Todo.to_string(), content, parent_id],
+        )?;
+        let id = tx.last_insert_rowid() as i32;
+        sync_references(&tx, id, content)?;
+        ids.push(id);
+    }
+    tx.commit()?;
+    Ok(ids)
+}
+
+/// Flips a boolean column of a thought and returns its new value, or None
+/// when the thought does not exist.
+fn toggle_flag(conn: &Connection, thought_id: i32, column: &str) -> Result<Option<bool>, AppError> {
+    let flag = conn
+        .query_row(
+            &format!("SELECT {} FROM thoughts WHERE id = ?", column),
+            [thought_id],
+            |row| row.get::<_, bool>(0),
+        )
+        .optional()?
+        .map(|flag| !flag);
+    if let Some(flag) = flag {
+        conn.execute(
+            &format!("UPDATE thoughts SET {} = ? WHERE id = ?", column),
+            params![flag, thought_id],
+        )?;
+    }
+    Ok(flag)
+}
+
+/// Pins or unpins a thought, returning whether it is now pinned.
+pub fn toggle_pinned(conn: &Connection, thought_id: i32) -> Result<Option<bool>, AppError> {
+    toggle_flag(conn, thought_id, "pinned")
+}
+
+/// Stars or unstars a thought, returning whether it is now starred.
+pub fn toggle_starred(conn: &Connection, thought_id: i32) -> Result<Option<bool>, AppError> {
+    toggle_flag(conn, thought_id, "starred")
+}
+
+/// Active pinned thoughts, reviewed or not, oldest first.
+pub fn pinned_thoughts(conn: &Connection) -> Result<Vec<Thought>, AppError> {
+    let thoughts = conn
+        .prepare(&format!(
+            "{} WHERE t.pinned AND t.state = ? ORDER BY t.id",
+            SELECT_THOUGHTS
+        ))?
+        .query_map([ThoughtState::Active.to_string()], thought_from_row)?
+        .collect::<SqlResult<Vec<Thought>>>()?;
+    Ok(thoughts)
+}
+
+/// Moves a thought to `state`. Returns false when the thought does not exist.
+pub fn set_state(
+    conn: &Connection,
+    thought_id: i32,
+    state: ThoughtState,
+) -> Result<bool, AppError> {
+    let updated = conn.execute(
+        "UPDATE thoughts SET state = ? WHERE id = ?",
+        params![state.to_string(), thought_id],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Permanently deletes every trashed thought, keeping each one's last
+/// version as a revision. Returns how many were deleted.
+pub fn empty_trash(conn: &Connection) -> Result<usize, AppError> {
+    let ids = conn
+        .prepare("SELECT id FROM thoughts WHERE state = ?")?
+        .query_map([ThoughtState::Trashed.to_string()], |row| row.get(0))?
+        .collect::<SqlResult<Vec<i32>>>()?;
+    for id in &ids {
+        delete_thought(conn, *id)?;
+    }
+    Ok(ids.len())
+}
+
+/// Stores the analysis for a thought, replacing any earlier one.
+pub fn save_analysis(conn: &Connection, thought_id: i32, analysis: &str) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO analyses (thought_id, content) VALUES (?1, ?2)
+            ON CONFLICT(thought_id) DO UPDATE SET content = ?2, created_at = CURRENT_TIMESTAMP",
+        params![thought_id, analysis],
+    )?;
+    Ok(())
+}
+
+/// Stores the AI answer to a question, replacing any earlier one.
+pub fn save_answer(conn: &Connection, thought_id: i32, answer: &str) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO answers (thought_id, content) VALUES (?1, ?2)
+            ON CONFLICT(thought_id) DO UPDATE SET content = ?2, created_at = CURRENT_TIMESTAMP",
+        params![thought_id, answer],
+    )?;
+    Ok(())
+}
+
+/// Marks a question answered, or open again. Returns false when `thought_id`
+/// is not a question.
+pub fn set_answered(conn: &Connection, thought_id: i32, answered: bool) -> Result<bool, AppError> {
+    let updated = conn.execute(
+        "UPDATE thoughts SET answered = ? WHERE id = ? AND type = ?",
+        params![answered, thought_id, ThoughtType::Question.to_string()],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Questions not yet marked answered, oldest first.
+pub fn open_questions(conn: &Connection) -> Result<Vec<Thought>, AppError> {
+    let thoughts = conn
+        .prepare(&format!(
+            "{} WHERE t.type = ? AND t.answered = false AND t.state = ? ORDER BY t.id",
+            SELECT_THOUGHTS
+        ))?
+        .query_map(
+            [
+                ThoughtType::Question.to_string(),
+                ThoughtState::Active.to_string(),
+            ],
+            thought_from_row,
+        )?
+        .collect::<SqlResult<Vec<Thought>>>()?;
+    Ok(thoughts)
+}
+
+/// Stores the structured score for a thought, replacing any earlier one.
+/// Risks and next steps are kept as JSON arrays.
+pub fn save_score(conn: &Connection, thought_id: i32, score: &IdeaScore) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO idea_scores
+            (thought_id, summary, scale, effort_estimate, novelty_score, risks, next_steps)
+            VALUES (?, ?, ?, ?, ?, ?, ?)",
+        params![
+            thought_id,
+            score.summary(),
+            score.scale(),
+            score.effort_estimate(),
+            score.novelty_score(),
+            serde_json::to_string(score.risks())?,
+            serde_json::to_string(score.next_steps())?,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Records a pending type and tag suggestion, keeping the current type as is.
+pub fn save_suggestion(
+    conn: &Connection,
+    thought_id: i32,
+    classification: &Classification,
+) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE thoughts SET suggested_type = ?, suggested_tags = ?, suggestion_status = ?
+            WHERE id = ?",
+        params![
+            classification.thought_type().to_string(),
+            classification.tags().join(","),
+            SUGGESTION_PENDING,
+            thought_id
+        ],
+    )?;
+    Ok(())
+}
+
+/// Thoughts with a suggestion awaiting accept or reject, oldest first.
+pub fn pending_suggestions(conn: &Connection) -> Result<Vec<Thought>, AppError> {
+    let thoughts = conn
+        .prepare(&format!(
+            "{} WHERE t.suggestion_status = ? AND t.state = ? ORDER BY t.id",
+            SELECT_THOUGHTS
+        ))?
+        .query_map(
+            [
+                SUGGESTION_PENDING.to_string(),
+                ThoughtState::Active.to_string(),
+            ],
+            thought_from_row,
+        )?
+        .collect::<SqlResult<Vec<Thought>>>()?;
+    Ok(thoughts)
+}
+
+/// Thoughts of the given type that have never been classified, oldest first.
+pub fn unclassified(
+    conn: &Connection,
+    thought_type: &ThoughtType,
+    limit: u32,
+) -> Result<Vec<Thought>, AppError> {
+    let thoughts = conn
+        .prepare(&format!(
+            "{} WHERE t.type = ? AND t.suggestion_status IS NULL AND t.state = ?
+                ORDER BY t.id LIMIT ?",
+            SELECT_THOUGHTS
+        ))?
+        .query_map(
+            params![
+                thought_type.to_string(),
+                ThoughtState::Active.to_string(),
+                limit
+            ],
+            thought_from_row,
+        )?
+        .collect::<SqlResult<Vec<Thought>>>()?;
+    Ok(thoughts)
+}
+
+/// Applies a pending suggestion: the suggested type replaces the current one,
+/// which is kept as `original_type`, and suggested tags are added to the
+/// existing ones. Returns false when there is no pending suggestion.
+pub fn accept_suggestion(conn: &Connection, thought_id: i32) -> Result<bool, AppError> {
+    let Some(thought) = get_thought(conn, thought_id)? else {
+        return Ok(false);
+    };
+    let Some(suggestion) = thought.suggestion() else {
+        return Ok(false);
+    };
+    let mut tags = thought.tags().to_vec();
+    for tag in suggestion.tags() {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+    conn.execute(
+        "UPDATE thoughts SET original_type = type, type = suggested_type, tags = ?,
+            suggestion_status = ? WHERE id = ?",
+        params![tags.join(","), SUGGESTION_ACCEPTED, thought_id],
+    )?;
+    Ok(true)
+}
+
+/// Dismisses a pending suggestion, leaving the thought unchanged. Returns
+/// false when there is no pending suggestion.
+pub fn reject_suggestion(conn: &Connection, thought_id: i32) -> Result<bool, AppError> {
+    let updated = conn.execute(
+        "UPDATE thoughts SET suggestion_status = ? WHERE id = ? AND suggestion_status = ?",
+        params![SUGGESTION_REJECTED, thought_id, SUGGESTION_PENDING],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Stores the embedding of a thought as little-endian `f32`s, replacing any
+/// earlier one.
+pub fn save_embedding(
+    conn: &Connection,
+    thought_id: i32,
+    model: &str,
+    vector: &[f32],
+) -> Result<(), AppError> {
+    let bytes: Vec<u8> = vector.iter().flat_map(|v| v.to_le_bytes()).collect();
+    conn.execute(
+        "INSERT OR REPLACE INTO embeddings (thought_id, model, vector) VALUES (?, ?, ?)",
+        params![thought_id, model, bytes],
+    )?;
+    Ok(())
+}
+
+/// Thoughts outside the trash without an embedding from `model`, oldest
+/// first.
+pub fn missing_embeddings(conn: &Connection, model: &str) -> Result<Vec<Thought>, AppError> {
+    let thoughts = conn
+        .prepare(&format!(
+            "{} WHERE t.id NOT IN (SELECT thought_id FROM embeddings WHERE model = ?)
+                AND t.state != ? ORDER BY t.id",
+            SELECT_THOUGHTS
+        ))?
+        .query_map(
+            [model.to_string(), ThoughtState::Trashed.to_string()],
+            thought_from_row,
+        )?
+        .collect::<SqlResult<Vec<Thought>>>()?;
+    Ok(thoughts)
+}
+
+/// Stored embeddings from `model` of thoughts outside the trash, keyed by
+/// thought id.
+pub fn load_embeddings(conn: &Connection, model: &str) -> Result<Vec<(i32, Vec<f32>)>, AppError> {
+    let embeddings = conn
+        .prepare(
+            "SELECT e.thought_id, e.vector FROM embeddings e
+                JOIN thoughts t ON t.id = e.thought_id
+                WHERE e.model = ? AND t.state != ? ORDER BY e.thought_id",
+        )?
+        .query_map(
+            [model.to_string(), ThoughtState::Trashed.to_string()],
+            |row| {
+                let bytes: Vec<u8> = row.get(1)?;
+                let vector = bytes
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+                Ok((row.get(0)?, vector))
+            },
+        )?
+        .collect::<SqlResult<Vec<(i32, Vec<f32>)>>>()?;
+    Ok(embeddings)
+}
+
+/// Records the tokens used by one provider call.
+pub fn save_usage(
+    conn: &Connection,
+    provider: &str,
+    model: &str,
+    tokens: &TokenUsage,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO ai_usage (provider, model, input_tokens, output_tokens) VALUES (?, ?, ?, ?)",
+        params![
+            provider,
+            model,
+            tokens.input_tokens(),
+            tokens.output_tokens()
+        ],
+    )?;
+    Ok(())
+}
+
+/// The cached provider response for `key`, if stored within the last
+/// `ttl_hours`.
+pub fn cached_response(
+    conn: &Connection,
+    key: &str,
+    ttl_hours: u64,
+) -> Result<Option<String>, AppError> {
+    let response = conn
+        .query_row(
+            "SELECT response FROM response_cache
+                WHERE key = ? AND created_at > datetime('now', ?)",
+            params![key, format!("-{} hours", ttl_hours)],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(response)
+}
+
+/// Stores a provider response under `key`, replacing an older one.
+pub fn save_cached_response(conn: &Connection, key: &str, response: &str) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO response_cache (key, response) VALUES (?, ?)",
+        params![key, response],
+    )?;
+    Ok(())
+}
+
+/// Calls and tokens per provider and model, for one month (`YYYY-MM`) or
+/// all time.
+pub fn usage_totals(conn: &Connection, month: Option<&str>) -> Result<Vec<UsageTotal>, AppError> {
+    let totals = conn
+        .prepare(
+            "SELECT provider, model, COUNT(*), SUM(input_tokens), SUM(output_tokens)
+                FROM ai_usage
+                WHERE ?1 IS NULL OR strftime('%Y-%m', created_at) = ?1
+                GROUP BY provider, model
+                ORDER BY provider, model",
+        )?
+        .query_map([month], |row| {
+            Ok(UsageTotal::new(
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                TokenUsage::new(row.get(3)?, row.get(4)?),
+            ))
+        })?
+        .collect::<SqlResult<Vec<UsageTotal>>>()?;
+    Ok(totals)
+}
+
+/// The current month in UTC as `YYYY-MM`, matching stored timestamps.
+pub fn current_month(conn: &Connection) -> Result<String, AppError> {
+    Ok(conn.query_row("SELECT strftime('%Y-%m', 'now')", [], |row| row.get(0))?)
+}
+
+/// Inserts the thought described by the writer arguments and returns its id.
+/// A parent that does not exist is a `NotFound` error.
+pub fn write_to_db(conn: &Connection, args: &Args) -> SqlResult<i32, AppError> {
+    if let Some(parent_id) = args.parent()
+        && get_thought(conn, parent_id)?.is_none()
+    {
+        return Err(AppError::NotFound(format!("parent thought #{}", parent_id)));
+    }
+    conn.execute(
+        "INSERT INTO thoughts (type, content, tags, due, priority, parent_id, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, datetime('now'))",
+        params![
+            args.thought_type().to_string(),
+            args.content(),
+            args.tags().join(","),
+            args.due(),
+            args.priority().map(|p| p.to_string()),
+            args.parent(),
+        ],
+    )?;
+    let id = conn.last_insert_rowid() as i32;
+    sync_references(conn, id, args.content())?;
+    Ok(id)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::writer_config::ThoughtType;
 
     fn create_in_memory_db() -> Connection {
         setup_db(":memory:").unwrap()
@@ -115,7 +1332,7 @@ mod tests {
         assert_eq!(thoughts.len(), 1);
         assert_eq!(thoughts[0].content(), "Unreviewed thought");
         // The thought struct still has reviewed: false as it was when fetched
-        assert_eq!(thoughts[0].reviewed(), false);
+        assert!(!thoughts[0].reviewed());
 
         // But in DB it should now be marked as reviewed
         let reviewed_count: i32 = conn
@@ -203,4 +1420,524 @@ mod tests {
         let thoughts = read(&conn).unwrap();
         assert_eq!(thoughts.len(), 0);
     }
+
+    #[test]
+    fn test_write_to_db_stores_tags_and_created_at() {
+        let conn = create_in_memory_db();
+        let args = Args::new_for_test(ThoughtType::Project, "Tagged".to_string())
+            .with_tags(&["rust", "cli"]);
+
+        write_to_db(&conn, &args).unwrap();
+
+        let thoughts = read(&conn).unwrap();
+        assert_eq!(thoughts[0].tags(), ["rust", "cli"]);
+        assert_eq!(thoughts[0].created_at().len(), 19);
+    }
+
+    #[test]
+    fn test_setup_db_migrates_existing_table() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        {
+            let conn = Connection::open(path).unwrap();
+            conn.execute(
+                "CREATE TABLE thoughts (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    type TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    reviewed BOOLEAN NOT NULL DEFAULT FALSE
+                )",
+                [],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO thoughts (type, content) VALUES ('Notes', 'Old thought')",
+                [],
+            )
+            .unwrap();
+        }
+
+        let conn = setup_db(path).unwrap();
+        let thoughts = read(&conn).unwrap();
+
+        assert_eq!(thoughts.len(), 1);
+        assert!(thoughts[0].tags().is_empty());
+        assert!(!thoughts[0].created_at().is_empty());
+    }
+
+    #[test]
+    fn test_save_analysis_attaches_to_thought() {
+        let conn = create_in_memory_db();
+        let args = Args::new_for_test(ThoughtType::Project, "Idea".to_string());
+        write_to_db(&conn, &args).unwrap();
+
+        save_analysis(&conn, 1, "First pass").unwrap();
+        save_analysis(&conn, 1, "Second pass").unwrap();
+
+        let thought = get_thought(&conn, 1).unwrap().unwrap();
+        assert_eq!(thought.analysis(), Some("Second pass"));
+        let count: i32 = conn
+            .query_row("SELECT COUNT(*) FROM analyses", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_get_thought_missing_returns_none() {
+        let conn = create_in_memory_db();
+        assert!(get_thought(&conn, 99).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_save_score_round_trips() {
+        let conn = create_in_memory_db();
+        let args = Args::new_for_test(ThoughtType::Project, "Idea".to_string());
+        write_to_db(&conn, &args).unwrap();
+        let score = IdeaScore::new(
+            "Summary".to_string(),
+            "startup".to_string(),
+            "6 months".to_string(),
+            8,
+            vec!["Funding".to_string()],
+            vec!["Talk to users".to_string(), "Build MVP".to_string()],
+        );
+
+        save_score(&conn, 1, &score).unwrap();
+
+        let thought = get_thought(&conn, 1).unwrap().unwrap();
+        assert_eq!(thought.score(), Some(&score));
+    }
+
+    #[test]
+    fn test_accept_suggestion_updates_type_and_merges_tags() {
+        let conn = create_in_memory_db();
+        let args = Args::new_for_test(ThoughtType::Misc, "Build a habit tracker".to_string())
+            .with_tags(&["cli"]);
+        let id = write_to_db(&conn, &args).unwrap();
+        let suggestion = Classification::new(
+            ThoughtType::Project,
+            vec!["cli".to_string(), "habits".to_string()],
+        );
+
+        save_suggestion(&conn, id, &suggestion).unwrap();
+        assert_eq!(pending_suggestions(&conn).unwrap().len(), 1);
+        assert!(accept_suggestion(&conn, id).unwrap());
+
+        let thought = get_thought(&conn, id).unwrap().unwrap();
+        assert_eq!(thought.thought_type(), &ThoughtType::Project);
+        assert_eq!(thought.tags(), ["cli", "habits"]);
+        assert!(thought.suggestion().is_none());
+        let original: String = conn
+            .query_row(
+                "SELECT original_type FROM thoughts WHERE id = ?",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(original, "Misc");
+        assert!(!accept_suggestion(&conn, id).unwrap());
+    }
+
+    #[test]
+    fn test_reject_suggestion_keeps_thought() {
+        let conn = create_in_memory_db();
+        let args = Args::new_for_test(ThoughtType::Misc, "Call the bank".to_string());
+        let id = write_to_db(&conn, &args).unwrap();
+        save_suggestion(&conn, id, &Classification::new(ThoughtType::Todo, vec![])).unwrap();
+
+        assert!(reject_suggestion(&conn, id).unwrap());
+
+        let thought = get_thought(&conn, id).unwrap().unwrap();
+        assert_eq!(thought.thought_type(), &ThoughtType::Misc);
+        assert!(pending_suggestions(&conn).unwrap().is_empty());
+        assert!(
+            unclassified(&conn, &ThoughtType::Misc, 10)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(!reject_suggestion(&conn, id).unwrap());
+    }
+
+    #[test]
+    fn test_embeddings_round_trip_per_model() {
+        let conn = create_in_memory_db();
+        for content in ["First", "Second"] {
+            write_to_db(
+                &conn,
+                &Args::new_for_test(ThoughtType::Notes, content.to_string()),
+            )
+            .unwrap();
+        }
+
+        save_embedding(&conn, 1, "small", &[0.25, -1.5, 3.0]).unwrap();
+
+        assert_eq!(
+            load_embeddings(&conn, "small").unwrap(),
+            [(1, vec![0.25, -1.5, 3.0])]
+        );
+        assert!(load_embeddings(&conn, "large").unwrap().is_empty());
+        let missing = missing_embeddings(&conn, "small").unwrap();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(*missing[0].id(), 2);
+        assert_eq!(missing_embeddings(&conn, "large").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_answers_and_open_questions() {
+        let conn = create_in_memory_db();
+        for (thought_type, content) in [
+            (ThoughtType::Question, "How do CRDTs merge?"),
+            (ThoughtType::Notes, "Not a question"),
+            (ThoughtType::Question, "What is a B-tree?"),
+        ] {
+            write_to_db(
+                &conn,
+                &Args::new_for_test(thought_type, content.to_string()),
+            )
+            .unwrap();
+        }
+
+        save_answer(&conn, 1, "By merging states").unwrap();
+        assert!(set_answered(&conn, 3, true).unwrap());
+        assert!(!set_answered(&conn, 2, true).unwrap());
+
+        let open = open_questions(&conn).unwrap();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].answer(), Some("By merging states"));
+        assert!(get_thought(&conn, 3).unwrap().unwrap().answered());
+
+        set_answered(&conn, 3, false).unwrap();
+        assert_eq!(open_questions(&conn).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_usage_totals_group_by_model_and_month() {
+        let conn = create_in_memory_db();
+        save_usage(&conn, "OpenAI", "gpt-4o-mini", &TokenUsage::new(100, 20)).unwrap();
+        save_usage(&conn, "OpenAI", "gpt-4o-mini", &TokenUsage::new(50, 10)).unwrap();
+        save_usage(&conn, "Ollama", "llama3.2", &TokenUsage::new(7, 3)).unwrap();
+        conn.execute(
+            "INSERT INTO ai_usage (provider, model, input_tokens, output_tokens, created_at)
+                VALUES ('OpenAI', 'gpt-4o-mini', 1000, 1000, '2020-01-15 10:00:00')",
+            [],
+        )
+        .unwrap();
+
+        let month = current_month(&conn).unwrap();
+        let totals = usage_totals(&conn, Some(&month)).unwrap();
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[1].model(), "gpt-4o-mini");
+        assert_eq!(totals[1].calls(), 2);
+        assert_eq!(totals[1].tokens(), &TokenUsage::new(150, 30));
+
+        let all = usage_totals(&conn, None).unwrap();
+        assert_eq!(all[1].tokens(), &TokenUsage::new(1150, 1030));
+        assert!(usage_totals(&conn, Some("2019-12")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cached_response_expires_after_ttl() {
+        let conn = create_in_memory_db();
+        save_cached_response(&conn, "fresh", "first").unwrap();
+        save_cached_response(&conn, "fresh", "second").unwrap();
+        conn.execute(
+            "INSERT INTO response_cache (key, response, created_at)
+                VALUES ('stale', 'old', datetime('now', '-2 hours'))",
+            [],
+        )
+        .unwrap();
+
+        assert_eq!(
+            cached_response(&conn, "fresh", 1).unwrap().as_deref(),
+            Some("second")
+        );
+        assert_eq!(cached_response(&conn, "stale", 1).unwrap(), None);
+        assert_eq!(
+            cached_response(&conn, "stale", 3).unwrap().as_deref(),
+            Some("old")
+        );
+        assert_eq!(cached_response(&conn, "missing", 24).unwrap(), None);
+    }
+
+    #[test]
+    fn test_edit_and_delete_keep_revisions() {
+        let conn = create_in_memory_db();
+        let args =
+            Args::new_for_test(ThoughtType::Misc, "Habbit tracker".to_string()).with_tags(&["cli"]);
+        write_to_db(&conn, &args).unwrap();
+        save_analysis(&conn, 1, "Small project").unwrap();
+        save_embedding(&conn, 1, "model", &[1.0]).unwrap();
+
+        assert!(edit_thought(&conn, 1, Some(&ThoughtType::Project), None).unwrap());
+        assert_eq!(load_embeddings(&conn, "model").unwrap().len(), 1);
+        assert!(edit_thought(&conn, 1, None, Some("Habit tracker")).unwrap());
+        assert!(load_embeddings(&conn, "model").unwrap().is_empty());
+        let thought = get_thought(&conn, 1).unwrap().unwrap();
+        assert_eq!(thought.thought_type(), &ThoughtType::Project);
+        assert_eq!(thought.content(), "Habit tracker");
+        assert_eq!(thought.tags(), ["cli"]);
+
+        assert!(delete_thought(&conn, 1).unwrap());
+        assert!(get_thought(&conn, 1).unwrap().is_none());
+        assert!(!delete_thought(&conn, 1).unwrap());
+        assert!(!edit_thought(&conn, 1, None, Some("Gone")).unwrap());
+        let revisions: Vec<(String, String, String)> = conn
+            .prepare("SELECT type, content, action FROM thought_revisions ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<SqlResult<_>>()
+            .unwrap();
+        assert_eq!(
+            revisions,
+            [
+                ("Misc".into(), "Habbit tracker".into(), "edit".into()),
+                ("Project".into(), "Habbit tracker".into(), "edit".into()),
+                ("Project".into(), "Habit tracker".into(), "delete".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_archived_and_trashed_thoughts_leave_read_paths() {
+        let conn = create_in_memory_db();
+        for content in ["Active", "Archived", "Trashed"] {
+            let args = Args::new_for_test(ThoughtType::Question, content.to_string());
+            write_to_db(&conn, &args).unwrap();
+            save_embedding(&conn, conn.last_insert_rowid() as i32, "model", &[1.0]).unwrap();
+        }
+
+        assert!(set_state(&conn, 2, ThoughtState::Archived).unwrap());
+        assert!(set_state(&conn, 3, ThoughtState::Trashed).unwrap());
+        assert!(!set_state(&conn, 9, ThoughtState::Trashed).unwrap());
+
+        let ids = |thoughts: Vec<Thought>| thoughts.iter().map(|t| *t.id()).collect::<Vec<_>>();
+        assert_eq!(ids(read_from_db(&conn).unwrap()), [1]);
+        assert_eq!(ids(open_questions(&conn).unwrap()), [1]);
+        let embedded: Vec<i32> = load_embeddings(&conn, "model")
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(embedded, [1, 2]);
+        assert_eq!(
+            get_thought(&conn, 3).unwrap().unwrap().state(),
+            ThoughtState::Trashed
+        );
+
+        assert_eq!(empty_trash(&conn).unwrap(), 1);
+        assert!(get_thought(&conn, 3).unwrap().is_none());
+        assert_eq!(empty_trash(&conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_rated_thoughts_come_back_when_due() {
+        let conn = create_in_memory_db();
+        for content in ["Rated", "Unrated"] {
+            let args = Args::new_for_test(ThoughtType::Notes, content.to_string());
+            write_to_db(&conn, &args).unwrap();
+        }
+
+        assert!(rate_thought(&conn, 1, 4).unwrap());
+        assert!(!rate_thought(&conn, 9, 4).unwrap());
+        let schedule = get_thought(&conn, 1)
+            .unwrap()
+            .unwrap()
+            .schedule()
+            .cloned()
+            .unwrap();
+        assert_eq!((schedule.repetitions(), schedule.interval_days()), (1, 1));
+        assert!(get_thought(&conn, 2).unwrap().unwrap().schedule().is_none());
+        assert!(due_for_review(&conn).unwrap().is_empty());
+
+        conn.execute(
+            "UPDATE thoughts SET next_review_at = datetime('now', '-1 hours') WHERE id = 1",
+            [],
+        )
+        .unwrap();
+        let due = due_for_review(&conn).unwrap();
+        assert_eq!(due.iter().map(|t| *t.id()).collect::<Vec<_>>(), [1]);
+        set_state(&conn, 1, ThoughtState::Archived).unwrap();
+        assert!(due_for_review(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_update_and_list_todos() {
+        let conn = create_in_memory_db();
+        for (thought_type, content) in [
+            (ThoughtType::Todo, "Undated"),
+            (ThoughtType::Todo, "Later"),
+            (ThoughtType::Todo, "Soon"),
+            (ThoughtType::Notes, "Not a todo"),
+        ] {
+            write_to_db(
+                &conn,
+                &Args::new_for_test(thought_type, content.to_string()),
+            )
+            .unwrap();
+        }
+
+        assert!(update_todo(&conn, 2, None, Some("2025-03-01"), false, None).unwrap());
+        let high = Some(Priority::High);
+        assert!(update_todo(&conn, 3, None, Some("2025-02-01"), false, high).unwrap());
+        assert!(!update_todo(&conn, 4, Some(TodoStatus::Done), None, false, None).unwrap());
+        let ids = |todos: Vec<Thought>| todos.iter().map(|t| *t.id()).collect::<Vec<_>>();
+        assert_eq!(ids(list_todos(&conn, None, None).unwrap()), [3, 2, 1]);
+        assert_eq!(
+            ids(list_todos(&conn, None, Some("2025-02-15")).unwrap()),
+            [3]
+        );
+
+        assert!(update_todo(&conn, 3, Some(TodoStatus::Done), None, true, None).unwrap());
+        let done = get_thought(&conn, 3).unwrap().unwrap();
+        let details = done.todo().unwrap();
+        assert_eq!(details.status(), TodoStatus::Done);
+        assert_eq!((details.due(), details.priority()), (None, high));
+        assert!(details.completed_at().is_some());
+        assert_eq!(ids(list_todos(&conn, None, None).unwrap()), [2, 1]);
+        assert_eq!(
+            ids(list_todos(&conn, Some(TodoStatus::Done), None).unwrap()),
+            [3]
+        );
+        assert!(get_thought(&conn, 4).unwrap().unwrap().todo().is_none());
+
+        update_todo(&conn, 3, Some(TodoStatus::InProgress), None, false, None).unwrap();
+        let reopened = get_thought(&conn, 3).unwrap().unwrap();
+        assert!(reopened.todo().unwrap().completed_at().is_none());
+    }
+
+    #[test]
+    fn test_references_follow_content_and_links_go_with_thought() {
+        let conn = create_in_memory_db();
+        for content in ["Question", "Project from [[#1]] and [[#9]]", "Todo"] {
+            write_to_db(
+                &conn,
+                &Args::new_for_test(ThoughtType::Notes, content.to_string()),
+            )
+            .unwrap();
+        }
+        let kinds = |links: Vec<(LinkKind, Thought)>| {
+            links
+                .iter()
+                .map(|(kind, t)| (*kind, *t.id()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            kinds(links_from(&conn, 2).unwrap()),
+            [(LinkKind::References, 1)]
+        );
+        assert!(add_link(&conn, 3, 2, LinkKind::DerivedFrom).unwrap());
+        assert!(!add_link(&conn, 3, 2, LinkKind::DerivedFrom).unwrap());
+        assert_eq!(
+            kinds(backlinks(&conn, 2).unwrap()),
+            [(LinkKind::DerivedFrom, 3)]
+        );
+
+        edit_thought(&conn, 2, None, Some("Project from [[#3]]")).unwrap();
+        assert_eq!(
+            kinds(links_from(&conn, 2).unwrap()),
+            [(LinkKind::References, 3)]
+        );
+        assert!(backlinks(&conn, 1).unwrap().is_empty());
+
+        set_state(&conn, 3, ThoughtState::Trashed).unwrap();
+        assert!(links_from(&conn, 2).unwrap().is_empty());
+        delete_thought(&conn, 3).unwrap();
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(*) FROM thought_links", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(remaining, 0);
+        assert_eq!(remove_link(&conn, 2, 3, None).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_sub_thoughts_roll_up_progress() {
+        let conn = create_in_memory_db();
+        let project = Args::new_for_test(ThoughtType::Project, "Garden".to_string());
+        write_to_db(&conn, &project).unwrap();
+        let milestone = Args::new_for_test(ThoughtType::Notes, "Beds".to_string()).with_parent(1);
+        write_to_db(&conn, &milestone).unwrap();
+        let steps = ["Buy wood".to_string(), "Build beds".to_string()];
+        assert_eq!(add_subtasks(&conn, 2, &steps).unwrap(), [3, 4]);
+        let water = Args::new_for_test(ThoughtType::Todo, "Water".to_string()).with_parent(1);
+        write_to_db(&conn, &water).unwrap();
+        update_todo(&conn, 3, Some(TodoStatus::Done), None, false, None).unwrap();
+
+        let tree: Vec<(usize, i32)> = descendants(&conn, 1, ThoughtState::Active)
+            .unwrap()
+            .iter()
+            .map(|(depth, t)| (*depth, *t.id()))
+            .collect();
+        assert_eq!(tree, [(1, 2), (2, 3), (2, 4), (1, 5)]);
+        assert_eq!(progress(&conn, 1).unwrap(), Some(Progress::new(1, 3)));
+        assert_eq!(progress(&conn, 2).unwrap(), Some(Progress::new(1, 2)));
+        assert_eq!(progress(&conn, 5).unwrap(), None);
+        let all = progress_of(&conn, &[1, 2, 5]).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[&1], Progress::new(1, 3));
+        assert_eq!(all[&2], Progress::new(1, 2));
+        assert!(is_descendant(&conn, 4, 1).unwrap());
+        assert!(!is_descendant(&conn, 1, 4).unwrap());
+
+        set_state(&conn, 4, ThoughtState::Archived).unwrap();
+        assert_eq!(progress(&conn, 1).unwrap(), Some(Progress::new(1, 2)));
+        delete_thought(&conn, 2).unwrap();
+        assert_eq!(get_thought(&conn, 3).unwrap().unwrap().parent_id(), Some(1));
+
+        let orphan = Args::new_for_test(ThoughtType::Todo, "Lost".to_string()).with_parent(42);
+        assert!(matches!(
+            write_to_db(&conn, &orphan),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_pinned_thoughts_stay_listed_after_review() {
+        let conn = create_in_memory_db();
+        for content in ["Tax deadlines", "Garden"] {
+            write_to_db(
+                &conn,
+                &Args::new_for_test(ThoughtType::Notes, content.to_string()),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(toggle_pinned(&conn, 1).unwrap(), Some(true));
+        assert_eq!(toggle_starred(&conn, 2).unwrap(), Some(true));
+        assert_eq!(toggle_pinned(&conn, 9).unwrap(), None);
+        read(&conn).unwrap();
+
+        let pinned = pinned_thoughts(&conn).unwrap();
+        assert_eq!(pinned.len(), 1);
+        assert!(pinned[0].pinned() && pinned[0].reviewed() && !pinned[0].starred());
+        assert!(get_thought(&conn, 2).unwrap().unwrap().starred());
+
+        set_state(&conn, 1, ThoughtState::Archived).unwrap();
+        assert!(pinned_thoughts(&conn).unwrap().is_empty());
+        set_state(&conn, 1, ThoughtState::Active).unwrap();
+        assert_eq!(toggle_pinned(&conn, 1).unwrap(), Some(false));
+        assert!(pinned_thoughts(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_spaced_resurfacing_caps_the_interval() {
+        let conn = create_in_memory_db();
+        conn.execute(
+            "INSERT INTO thoughts (type, content, reviewed, created_at, resurfaced_at,
+                resurface_count) VALUES ('Notes', 'Old', true,
+                datetime('now', '-100000 days'), datetime('now', '-1 days'), 200)",
+            [],
+        )
+        .unwrap();
+        let spaced = || resurface_candidates(&conn, ResurfaceMode::Spaced, 60, 5).unwrap();
+
+        assert!(spaced().is_empty());
+        conn.execute(
+            "UPDATE thoughts SET resurfaced_at = datetime('now', '-70000 days')",
+            [],
+        )
+        .unwrap();
+        assert_eq!(spaced().len(), 1);
+    }
 }
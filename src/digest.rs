@@ -0,0 +1,573 @@
+use crate::client::Session;
+use crate::db_operations::{
+    due_for_review, list_todos, mark_resurfaced, open_questions, pinned_thoughts, progress_of,
+    read, resurface_candidates, save_analysis, save_answer, save_score,
+};
+use crate::email;
+use crate::embeddings;
+use crate::errors::AppError;
+use crate::ics;
+use crate::prompt::PromptTemplate;
+use crate::reader_config::{AnalysisMode, Config, DigestConfig, ResurfaceConfig};
+use crate::scoring::{IdeaScore, STRUCTURED_INSTRUCTIONS, idea_schema};
+use crate::themes;
+use crate::thought::{EmailAttachment, EmailSection, Thought, ThoughtsEmailBody};
+use crate::todo::{self, TodoDetails};
+use crate::usage;
+use crate::writer_config::ThoughtType;
+use chrono::{Days, NaiveDate, Utc};
+use rusqlite::Connection;
+use std::cmp::Reverse;
+
+/// Sends the configured thought types for AI analysis. In per-thought mode
+/// every thought is analysed on its own and the result is stored and attached
+/// to it; in batch mode each type is sent in as few prompts as fit the batch
+/// token budget and the merged results are printed once, without streaming
+/// the responses as they arrive. In structured mode each thought is scored
+/// against the idea schema; a response that fails validation is kept as a
+/// plain analysis instead. In per-thought and structured mode a failed
+/// request is reported and the thought goes out without analysis.
+pub fn analyse(
+    conn: &Connection,
+    config: &Config,
+    thoughts: &mut [Thought],
+) -> Result<(), AppError> {
+    let session = Session::from_config(conn, config);
+    let prompts = config.prompts();
+    for thought_type in prompts.analyse() {
+        if !thoughts.iter().any(|t| t.thought_type() == thought_type) {
+            continue;
+        }
+        let template = PromptTemplate::for_type(prompts, thought_type)?;
+        match prompts.mode() {
+            AnalysisMode::Batch => {
+                let session = Session::from_config(conn, config).with_streaming(false);
+                let selected: Vec<&Thought> = thoughts
+                    .iter()
+                    .filter(|t| t.thought_type() == thought_type)
+                    .collect();
+                let mut results = Vec::new();
+                for batch in template.batches(&selected, prompts.max_batch_tokens()) {
+                    results.extend(analyse_batch(&session, &template, &batch)?);
+                }
+                println!("{} analysis:\n{}", thought_type, results.join("\n\n"));
+            }
+            AnalysisMode::PerThought => {
+                for thought in thoughts
+                    .iter_mut()
+                    .filter(|t| t.thought_type() == thought_type)
+                {
+                    match session.response(&template.render(&[thought])) {
+                        Ok(analysis) => {
+                            save_analysis(conn, *thought.id(), &analysis)?;
+                            thought.set_analysis(analysis);
+                        }
+                        Err(e) => eprintln!("Could not analyse thought #{}: {}", thought.id(), e),
+                    }
+                }
+            }
+            AnalysisMode::Structured => {
+                let schema = idea_schema();
+                for thought in thoughts
+                    .iter_mut()
+                    .filter(|t| t.thought_type() == thought_type)
+                {
+                    let prompt = template.render(&[thought]) + STRUCTURED_INSTRUCTIONS;
+                    let response = match session.structured_response(&prompt, &schema) {
+                        Ok(response) => response,
+                        Err(e) => {
+                            eprintln!("Could not score thought #{}: {}", thought.id(), e);
+                            continue;
+                        }
+                    };
+                    match IdeaScore::from_response(&response) {
+                        Ok(score) => {
+                            save_score(conn, *thought.id(), &score)?;
+                            thought.set_score(score);
+                        }
+                        Err(e) => {
+                            eprintln!(
+                                "Thought #{}: invalid structured response: {}",
+                                thought.id(),
+                                e
+                            );
+                            save_analysis(conn, *thought.id(), &response)?;
+                            thought.set_analysis(response);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sends one batch of thoughts, halving it and trying again while the
+/// response is cut off at the output token limit. A single thought whose
+/// analysis still does not fit is reported and left out.
+fn analyse_batch(
+    session: &Session,
+    template: &PromptTemplate,
+    batch: &[&Thought],
+) -> Result<Vec<String>, AppError> {
+    match session.response(&template.render(batch)) {
+        Err(AppError::Truncated(_)) if batch.len() > 1 => {
+            let (first, second) = batch.split_at(batch.len() / 2);
+            let mut results = analyse_batch(session, template, first)?;
+            results.extend(analyse_batch(session, template, second)?);
+            Ok(results)
+        }
+        Err(AppError::Truncated(e)) => {
+            for thought in batch {
+                eprintln!("Could not analyse thought #{}: {}", thought.id(), e);
+            }
+            Ok(Vec::new())
+        }
+        result => result.map(|text| vec![text]),
+    }
+}
+
+/// Asks the provider to answer each open question without a stored answer,
+/// from this digest or an earlier one, and attaches the answers to this
+/// digest's thoughts. A failure is reported and leaves the question for the
+/// next run.
+pub fn answer_questions(
+    conn: &Connection,
+    config: &Config,
+    thoughts: &mut [Thought],
+) -> Result<(), AppError> {
+    let template = match config.questions().prompt() {
+        Some(path) => PromptTemplate::from_file(path)?,
+        None => PromptTemplate::builtin_answer(),
+    };
+    let session = Session::from_config(conn, config);
+    for question in open_questions(conn)?
+        .iter()
+        .filter(|q| q.answer().is_none())
+    {
+        match session.response(&template.render(&[question])) {
+            Ok(answer) => {
+                save_answer(conn, *question.id(), &answer)?;
+                if let Some(thought) = thoughts.iter_mut().find(|t| t.id() == question.id()) {
+                    thought.set_answer(answer);
+                }
+            }
+            Err(e) => eprintln!("Could not answer question #{}: {}", question.id(), e),
+        }
+    }
+    Ok(())
+}
+
+/// Questions from earlier digests that are still not marked answered.
+pub fn still_open(conn: &Connection, thoughts: &[Thought]) -> Result<EmailSection, AppError> {
+    let items = open_questions(conn)?
+        .iter()
+        .filter(|q| !thoughts.iter().any(|t| t.id() == q.id()))
+        .map(|q| format!("#{} {} ({})", q.id(), q.content(), q.created_on()))
+        .collect();
+    Ok(EmailSection::new("Open questions", items))
+}
+
+fn listing(thought: &Thought) -> String {
+    format!(
+        "#{} [{}] {} ({})",
+        thought.id(),
+        thought.thought_type(),
+        thought.content(),
+        thought.created_on()
+    )
+}
+
+fn todo_listing(thought: &Thought, details: &TodoDetails) -> String {
+    let mut notes: Vec<String> = details
+        .due()
+        .map(|due| format!("due {}", due))
+        .into_iter()
+        .collect();
+    if let Some(priority) = details.priority() {
+        notes.push(format!("{} priority", priority));
+    }
+    if details.status() != todo::TodoStatus::Open {
+        notes.push(details.status().to_string());
+    }
+    format!(
+        "#{} {} ({})",
+        thought.id(),
+        thought.content(),
+        notes.join(", ")
+    )
+}
+
+/// Unfinished todos due before `today`, then those due within
+/// `upcoming_days` counting today, most urgent first.
+pub fn agenda(
+    conn: &Connection,
+    today: NaiveDate,
+    upcoming_days: u32,
+) -> Result<Vec<EmailSection>, AppError> {
+    let last_day = today
+        .checked_add_days(Days::new(upcoming_days.saturating_sub(1).into()))
+        .unwrap_or(today);
+    let (overdue, upcoming): (Vec<Thought>, Vec<Thought>) =
+        list_todos(conn, None, Some(&todo::date(last_day)))?
+            .into_iter()
+            .partition(|t| t.todo().is_some_and(|d| d.overdue(today)));
+    let items = |todos: &[Thought]| {
+        todos
+            .iter()
+            .filter_map(|t| t.todo().map(|details| todo_listing(t, details)))
+            .collect()
+    };
+    let mut sections = vec![EmailSection::new("Overdue todos", items(&overdue))];
+    if upcoming_days > 0 {
+        sections.push(EmailSection::new("Due soon", items(&upcoming)));
+    }
+    Ok(sections)
+}
+
+/// This digest's thoughts: the unreviewed ones, marked reviewed on the way,
+/// followed by rated thoughts whose next SM-2 review has come. Those stay
+/// due until rated again. Pinned thoughts are left out, as they have their
+/// own section.
+pub fn select(conn: &Connection) -> Result<Vec<Thought>, AppError> {
+    let mut thoughts = read(conn)?;
+    for due in due_for_review(conn)? {
+        if !thoughts.iter().any(|t| t.id() == due.id()) {
+            thoughts.push(due);
+        }
+    }
+    thoughts.retain(|t| !t.pinned());
+    Ok(thoughts)
+}
+
+/// Older thoughts picked to be shown again, excluding this digest's own.
+/// Picked thoughts are recorded so spaced resurfacing moves on.
+pub fn resurface(
+    conn: &Connection,
+    settings: &ResurfaceConfig,
+    thoughts: &[Thought],
+) -> Result<EmailSection, AppError> {
+    let picked: Vec<Thought> = resurface_candidates(
+        conn,
+        settings.mode(),
+        settings.min_age_days(),
+        settings.count() + thoughts.len() as u32,
+    )?
+    .into_iter()
+    .filter(|old| !thoughts.iter().any(|t| t.id() == old.id()))
+    .take(settings.count() as usize)
+    .collect();
+    mark_resurfaced(conn, &picked)?;
+    let items = picked.iter().map(listing).collect();
+    Ok(EmailSection::new("Worth another look", items))
+}
+
+/// Drops scored project ideas below the minimum novelty score and, when
+/// enabled, moves scored ideas to the front, highest novelty first. Other
+/// thoughts keep their order.
+pub fn rank_ideas(thoughts: &mut Vec<Thought>, digest: &DigestConfig) {
+    let is_scored_project =
+        |t: &Thought| matches!(t.thought_type(), ThoughtType::Project) && t.score().is_some();
+    if let Some(min) = digest.min_novelty_score() {
+        thoughts.retain(|t| {
+            !is_scored_project(t) || t.score().is_some_and(|s| s.novelty_score() >= min)
+        });
+    }
+    if digest.sort_by_score() {
+        thoughts.sort_by_key(|t| {
+            Reverse(
+                t.score()
+                    .filter(|_| is_scored_project(t))
+                    .map_or(0, |s| s.novelty_score()),
+            )
+        });
+    }
+}
+
+/// Moves sub-thoughts right after their parent, depth first, when both are
+/// in the digest. Top-level thoughts and siblings keep their order.
+pub fn nest(thoughts: Vec<Thought>) -> Vec<Thought> {
+    let ids: Vec<i32> = thoughts.iter().map(|t| *t.id()).collect();
+    let (mut pending, roots): (Vec<Thought>, Vec<Thought>) = thoughts
+        .into_iter()
+        .partition(|t| t.parent_id().is_some_and(|p| ids.contains(&p)));
+    let mut nested = Vec::new();
+    let mut stack: Vec<Thought> = roots.into_iter().rev().collect();
+    while let Some(thought) = stack.pop() {
+        let (children, rest) = pending
+            .into_iter()
+            .partition(|t| t.parent_id() == Some(*thought.id()));
+        pending = rest;
+        stack.extend(children.into_iter().rev());
+        nested.push(thought);
+    }
+    nested
+}
+
+/// Reads unreviewed thoughts, analyses them and mails the weekly digest.
+/// Similar-thought detection and theme grouping are optional, so their
+/// failures only warn. Once the monthly AI budget is spent the digest is
+/// sent without any AI work.
+pub fn run(conn: &Connection, config: &Config) -> Result<(), AppError> {
+    let mut thoughts = select(conn)?;
+    let use_ai = match usage::over_budget(conn, config.usage())? {
+        Some((spent, budget)) => {
+            eprintln!(
+                "Monthly AI budget reached (${:.2} of ${:.2}), skipping AI analysis",
+                spent, budget
+            );
+            false
+        }
+        None => true,
+    };
+    if use_ai {
+        analyse(conn, config, &mut thoughts)?;
+    }
+    rank_ideas(&mut thoughts, config.digest());
+    let mut thoughts = nest(thoughts);
+    let ids: Vec<i32> = thoughts.iter().map(|t| *t.id()).collect();
+    let mut progress = progress_of(conn, &ids)?;
+    for thought in thoughts.iter_mut() {
+        if let Some(progress) = progress.remove(thought.id()) {
+            thought.set_progress(progress);
+        }
+    }
+    let mut sections = Vec::new();
+    if config.questions().answer() {
+        if use_ai {
+            answer_questions(conn, config, &mut thoughts)?;
+        }
+        sections.push(still_open(conn, &thoughts)?);
+    }
+    if config.resurface().count() > 0 {
+        sections.push(resurface(conn, config.resurface(), &thoughts)?);
+    }
+    if use_ai && config.embeddings().enabled() {
+        match embeddings::seen_before(conn, config, &thoughts) {
+            Ok(section) => sections.push(section),
+            Err(e) => eprintln!("Skipping similar thoughts: {}", e),
+        }
+    }
+    let mut themes = Vec::new();
+    if use_ai && config.digest().group_by_theme() {
+        match themes::group(conn, config, &thoughts) {
+            Ok(grouped) => themes = grouped,
+            Err(e) => eprintln!("Skipping themes: {}", e),
+        }
+    }
+    let todos = config.todos();
+    let agenda = agenda(conn, todo::today(), todos.upcoming_days())?;
+    let pinned = pinned_thoughts(conn)?;
+    let mut body = ThoughtsEmailBody::new(&thoughts)
+        .with_pinned(&pinned)
+        .with_leading_sections(agenda)
+        .with_themes(themes)
+        .with_sections(sections);
+    if todos.attach_ics() {
+        let calendar = ics::calendar(
+            &list_todos(conn, None, None)?,
+            Utc::now(),
+            todos.ics_events(),
+        );
+        body = body.with_attachment(EmailAttachment::new(
+            "todos.ics",
+            "text/calendar; charset=utf-8",
+            calendar,
+        ));
+    }
+    email::send_email(body, config.email_config(), config.retry())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scored(id: i32, novelty_score: u8) -> Thought {
+        let score = IdeaScore::new(
+            String::new(),
+            "hobby".to_string(),
+            String::new(),
+            novelty_score,
+            vec![],
+            vec![],
+        );
+        Thought::new(id, ThoughtType::Project, format!("Idea {}", id), false)
+            .with_score(Some(score))
+    }
+
+    fn ids(thoughts: &[Thought]) -> Vec<i32> {
+        thoughts.iter().map(|t| *t.id()).collect()
+    }
+
+    #[test]
+    fn test_rank_ideas_sorts_by_novelty() {
+        let mut thoughts = vec![
+            Thought::new(1, ThoughtType::Notes, "Note".to_string(), false),
+            scored(2, 3),
+            scored(3, 9),
+            Thought::new(4, ThoughtType::Todo, "Todo".to_string(), false),
+        ];
+
+        rank_ideas(&mut thoughts, &DigestConfig::default());
+
+        assert_eq!(ids(&thoughts), [3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn test_rank_ideas_filters_below_minimum() {
+        let digest: DigestConfig =
+            toml::from_str("min_novelty_score = 5\nsort_by_score = false").unwrap();
+        let mut thoughts = vec![
+            scored(1, 4),
+            Thought::new(2, ThoughtType::Project, "Unscored".to_string(), false),
+            scored(3, 5),
+        ];
+
+        rank_ideas(&mut thoughts, &digest);
+
+        assert_eq!(ids(&thoughts), [2, 3]);
+    }
+
+    #[test]
+    fn test_resurface_spaces_out_old_thoughts() {
+        let conn = crate::db_operations::setup_db(":memory:").unwrap();
+        for (content, age, reviewed) in [
+            ("Old", 100, true),
+            ("Recent", 10, true),
+            ("New", 100, false),
+        ] {
+            conn.execute(
+                "INSERT INTO thoughts (type, content, reviewed, created_at)
+                    VALUES ('Project', ?, ?, datetime('now', '-' || ? || ' days'))",
+                rusqlite::params![content, reviewed, age],
+            )
+            .unwrap();
+        }
+        let settings: ResurfaceConfig = toml::from_str("count = 2").unwrap();
+        let backdate = |days: i32| {
+            conn.execute(
+                "UPDATE thoughts SET resurfaced_at = datetime('now', '-' || ? || ' days')",
+                [days],
+            )
+            .unwrap();
+        };
+
+        let section = resurface(&conn, &settings, &[]).unwrap();
+        assert_eq!(section.items().len(), 1);
+        assert!(section.items()[0].starts_with("#1 [Project] Old"));
+        assert!(resurface(&conn, &settings, &[]).unwrap().items().is_empty());
+        backdate(40);
+        assert!(resurface(&conn, &settings, &[]).unwrap().items().is_empty());
+        backdate(70);
+        assert_eq!(resurface(&conn, &settings, &[]).unwrap().items().len(), 1);
+
+        let random: ResurfaceConfig = toml::from_str("count = 2\nmode = \"random\"").unwrap();
+        let this_week = [Thought::new(
+            1,
+            ThoughtType::Project,
+            "Old".to_string(),
+            true,
+        )];
+        assert!(
+            resurface(&conn, &random, &this_week)
+                .unwrap()
+                .items()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_select_adds_reviewed_thoughts_due_again() {
+        let conn = crate::db_operations::setup_db(":memory:").unwrap();
+        for (content, reviewed, next_review) in [
+            ("New", false, None),
+            ("Due", true, Some("-1 days")),
+            ("Later", true, Some("+3 days")),
+            ("Done", true, None),
+        ] {
+            conn.execute(
+                "INSERT INTO thoughts (type, content, reviewed, next_review_at)
+                    VALUES ('Notes', ?, ?, datetime('now', ?))",
+                rusqlite::params![content, reviewed, next_review],
+            )
+            .unwrap();
+        }
+
+        let contents = |thoughts: Vec<Thought>| -> Vec<String> {
+            thoughts.iter().map(|t| t.content().to_string()).collect()
+        };
+        assert_eq!(contents(select(&conn).unwrap()), ["New", "Due"]);
+        assert_eq!(contents(select(&conn).unwrap()), ["Due"]);
+    }
+
+    #[test]
+    fn test_select_leaves_pinned_thoughts_to_their_section() {
+        let conn = crate::db_operations::setup_db(":memory:").unwrap();
+        for (content, pinned) in [("Tax deadlines", true), ("Garden", false)] {
+            conn.execute(
+                "INSERT INTO thoughts (type, content, pinned) VALUES ('Notes', ?, ?)",
+                rusqlite::params![content, pinned],
+            )
+            .unwrap();
+        }
+
+        let thoughts = select(&conn).unwrap();
+        let pinned = pinned_thoughts(&conn).unwrap();
+        let html = ThoughtsEmailBody::new(&thoughts)
+            .with_pinned(&pinned)
+            .html();
+
+        assert_eq!(thoughts.len(), 1);
+        assert_eq!(thoughts[0].content(), "Garden");
+        assert_eq!(html.matches("Tax deadlines").count(), 1);
+    }
+
+    #[test]
+    fn test_agenda_lists_overdue_then_upcoming_todos() {
+        let conn = crate::db_operations::setup_db(":memory:").unwrap();
+        for (content, due, status) in [
+            ("Pay rent", Some("2025-01-10"), "open"),
+            ("Book flights", Some("2025-01-17"), "in-progress"),
+            ("Renew passport", Some("2025-02-01"), "open"),
+            ("Old chore", Some("2025-01-01"), "done"),
+            ("Someday", None, "open"),
+        ] {
+            conn.execute(
+                "INSERT INTO thoughts (type, content, due, status, priority)
+                    VALUES ('Todo', ?, ?, ?, 'high')",
+                rusqlite::params![content, due, status],
+            )
+            .unwrap();
+        }
+        let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        let sections = agenda(&conn, today, 7).unwrap();
+
+        assert_eq!(sections[0].title(), "Overdue todos");
+        assert_eq!(
+            sections[0].items(),
+            ["#1 Pay rent (due 2025-01-10, high priority)"]
+        );
+        assert_eq!(
+            sections[1].items(),
+            ["#2 Book flights (due 2025-01-17, high priority, in-progress)"]
+        );
+        assert_eq!(agenda(&conn, today, 0).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_nest_moves_sub_thoughts_under_their_parent() {
+        let thought = |id, parent| {
+            Thought::new(id, ThoughtType::Todo, format!("Todo {}", id), false).with_parent(parent)
+        };
+        let thoughts = vec![
+            thought(4, Some(2)),
+            thought(1, None),
+            thought(5, Some(9)),
+            thought(2, Some(1)),
+            thought(3, Some(1)),
+        ];
+
+        assert_eq!(ids(&nest(thoughts)), [1, 2, 4, 3, 5]);
+    }
+}
@@ -1,29 +1,48 @@
 use crate::errors::AppError;
 use crate::errors::AppError::SmtpEmail;
-use crate::reader_config::{Config, EmailConfig};
-use crate::thought::{Thought, ThoughtsEmailBody};
-use lettre::message::{Mailbox, header::ContentType};
-use lettre::transport::smtp::authentication::Credentials;
+use crate::reader_config::{EmailConfig, RetryConfig};
+use crate::retry::{RetryError, retry};
+use crate::thought::ThoughtsEmailBody;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart, header::ContentType};
+use lettre::transport::smtp::{self, authentication::Credentials};
 use lettre::{Message, SmtpTransport, Transport};
+use std::fmt::Display;
+
+/// A failed send that knows whether trying again can help.
+pub trait SendError: Display {
+    /// Whether the server refused the email for good, e.g. bad credentials
+    /// or an unknown recipient.
+    fn is_permanent(&self) -> bool;
+}
+
+impl SendError for smtp::Error {
+    fn is_permanent(&self) -> bool {
+        smtp::Error::is_permanent(self)
+    }
+}
 
 pub trait EmailTransport {
-    fn send(&self, email: &Message) -> Result<(), String>;
+    type Error: SendError;
+    fn send(&self, email: &Message) -> Result<(), Self::Error>;
 }
 
 impl EmailTransport for SmtpTransport {
-    fn send(&self, email: &Message) -> Result<(), String> {
-        Transport::send(self, email)
-            .map(|_| ())
-            .map_err(|e| e.to_string())
+    type Error = smtp::Error;
+    fn send(&self, email: &Message) -> Result<(), smtp::Error> {
+        Transport::send(self, email).map(|_| ())
     }
 }
 
+/// Wraps the digest body in an email, multipart when it has attachments, and
+/// sends it, retrying failed sends according to the retry policy. A
+/// permanent rejection is not retried.
 pub fn send_email_with_transport<T: EmailTransport>(
-    thought: &[Thought],
+    body: ThoughtsEmailBody,
     config: &EmailConfig,
     transport: &T,
+    retry_config: &RetryConfig,
 ) -> Result<(), AppError> {
-    let email = Message::builder()
+    let builder = Message::builder()
         .from(Mailbox::new(
             Some("Thought App".to_string()),
             config.sender_email().parse()?,
@@ -32,14 +51,40 @@ pub fn send_email_with_transport<T: EmailTransport>(
             Some(config.name().to_string()),
             config.receiver_email().parse()?,
         ))
-        .subject("Thought App, Weekly Round up")
-        .header(ContentType::TEXT_HTML)
-        .body(ThoughtsEmailBody::new(thought))?;
+        .subject("Thought App, Weekly Round up");
+    let email = if body.attachments().is_empty() {
+        builder.header(ContentType::TEXT_HTML).body(body)?
+    } else {
+        let mut parts = MultiPart::mixed().singlepart(SinglePart::html(body.html()));
+        for attachment in body.attachments() {
+            let content_type = ContentType::parse(attachment.content_type())
+                .map_err(|e| SmtpEmail(format!("{}: {}", attachment.filename(), e)))?;
+            parts = parts.singlepart(
+                Attachment::new(attachment.filename().to_string())
+                    .body(attachment.content().to_string(), content_type),
+            );
+        }
+        builder.multipart(parts)?
+    };
 
-    transport.send(&email).map_err(|e| AppError::SmtpEmail(e))
+    retry(retry_config, || {
+        transport.send(&email).map_err(|e| {
+            let permanent = e.is_permanent();
+            let error = AppError::SmtpEmail(e.to_string());
+            if permanent {
+                RetryError::permanent(error)
+            } else {
+                RetryError::transient(error)
+            }
+        })
+    })
 }
 
-pub fn send_email(thought: &[Thought], config: &EmailConfig) -> Result<(), AppError> {
+pub fn send_email(
+    body: ThoughtsEmailBody,
+    config: &EmailConfig,
+    retry_config: &RetryConfig,
+) -> Result<(), AppError> {
     let creds = Credentials::new(
         config.sender_email().to_string(),
         config.app_password().to_string(),
@@ -50,24 +95,87 @@ pub fn send_email(thought: &[Thought], config: &EmailConfig) -> Result<(), AppEr
         .credentials(creds)
         .build();
 
-    send_email_with_transport(thought, config, &mailer)
+    send_email_with_transport(body, config, &mailer, retry_config)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::thought::{EmailAttachment, Thought};
     use crate::writer_config::ThoughtType;
-    use std::cell::RefCell;
+    use std::cell::{Cell, RefCell};
 
     struct MockEmailTransport {
         should_fail: bool,
         sent_emails: RefCell<Vec<String>>,
     }
 
+    /// Keeps the formatted messages it is asked to send.
+    struct CapturingEmailTransport {
+        sent: RefCell<Vec<String>>,
+    }
+
+    /// A send failure with a fixed message and classification.
+    struct MockSendError {
+        message: &'static str,
+        permanent: bool,
+    }
+
+    impl std::fmt::Display for MockSendError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(self.message)
+        }
+    }
+
+    impl SendError for MockSendError {
+        fn is_permanent(&self) -> bool {
+            self.permanent
+        }
+    }
+
+    impl EmailTransport for CapturingEmailTransport {
+        type Error = MockSendError;
+        fn send(&self, email: &Message) -> Result<(), MockSendError> {
+            let formatted = String::from_utf8_lossy(&email.formatted()).to_string();
+            self.sent.borrow_mut().push(formatted);
+            Ok(())
+        }
+    }
+
+    struct FlakyEmailTransport {
+        failures_left: Cell<u32>,
+        attempts: Cell<u32>,
+        permanent: bool,
+    }
+
+    impl EmailTransport for FlakyEmailTransport {
+        type Error = MockSendError;
+        fn send(&self, _email: &Message) -> Result<(), MockSendError> {
+            self.attempts.set(self.attempts.get() + 1);
+            if self.failures_left.get() > 0 {
+                self.failures_left.set(self.failures_left.get() - 1);
+                Err(MockSendError {
+                    message: "Connection dropped",
+                    permanent: self.permanent,
+                })
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn test_retry_config() -> RetryConfig {
+        toml::from_str("max_attempts = 3\ninitial_backoff_ms = 1").unwrap()
+    }
+
     impl EmailTransport for MockEmailTransport {
-        fn send(&self, _email: &Message) -> Result<(), String> {
+        type Error = MockSendError;
+        fn send(&self, _email: &Message) -> Result<(), MockSendError> {
             if self.should_fail {
-                Err("Mock SMTP error".to_string())
+                Err(MockSendError {
+                    message: "Mock SMTP error",
+                    permanent: false,
+                })
             } else {
                 self.sent_emails.borrow_mut().push("sent".to_string());
                 Ok(())
@@ -103,7 +211,12 @@ mod tests {
             sent_emails: RefCell::new(Vec::new()),
         };
 
-        let result = send_email_with_transport(&thoughts, &config, &transport);
+        let result = send_email_with_transport(
+            ThoughtsEmailBody::new(&thoughts),
+            &config,
+            &transport,
+            &test_retry_config(),
+        );
         assert!(result.is_ok());
         assert_eq!(transport.sent_emails.borrow().len(), 1);
     }
@@ -123,7 +236,12 @@ mod tests {
             sent_emails: RefCell::new(Vec::new()),
         };
 
-        let result = send_email_with_transport(&thoughts, &config, &transport);
+        let result = send_email_with_transport(
+            ThoughtsEmailBody::new(&thoughts),
+            &config,
+            &transport,
+            &test_retry_config(),
+        );
         assert!(result.is_err());
         assert_eq!(transport.sent_emails.borrow().len(), 0);
     }
@@ -138,7 +256,12 @@ mod tests {
             sent_emails: RefCell::new(Vec::new()),
         };
 
-        let result = send_email_with_transport(&thoughts, &config, &transport);
+        let result = send_email_with_transport(
+            ThoughtsEmailBody::new(&thoughts),
+            &config,
+            &transport,
+            &test_retry_config(),
+        );
         assert!(result.is_ok());
         assert_eq!(transport.sent_emails.borrow().len(), 1);
     }
@@ -157,7 +280,12 @@ mod tests {
             sent_emails: RefCell::new(Vec::new()),
         };
 
-        let result = send_email_with_transport(&thoughts, &config, &transport);
+        let result = send_email_with_transport(
+            ThoughtsEmailBody::new(&thoughts),
+            &config,
+            &transport,
+            &test_retry_config(),
+        );
         assert!(result.is_ok());
         assert_eq!(transport.sent_emails.borrow().len(), 1);
     }
@@ -186,7 +314,96 @@ mod tests {
             sent_emails: RefCell::new(Vec::new()),
         };
 
-        let result = send_email_with_transport(&thoughts, &invalid_config, &transport);
+        let result = send_email_with_transport(
+            ThoughtsEmailBody::new(&thoughts),
+            &invalid_config,
+            &transport,
+            &test_retry_config(),
+        );
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_send_email_with_attachment_is_multipart() {
+        let config = create_test_email_config();
+        let thoughts: Vec<Thought> = vec![];
+        let transport = CapturingEmailTransport {
+            sent: RefCell::new(Vec::new()),
+        };
+        let calendar = "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n".to_string();
+        let body = ThoughtsEmailBody::new(&thoughts).with_attachment(EmailAttachment::new(
+            "todos.ics",
+            "text/calendar; charset=utf-8",
+            calendar,
+        ));
+
+        send_email_with_transport(body, &config, &transport, &test_retry_config()).unwrap();
+
+        let sent = transport.sent.borrow();
+        assert!(sent[0].contains("Content-Type: multipart/mixed"));
+        assert!(sent[0].contains("Content-Disposition: attachment; filename=\"todos.ics\""));
+        assert!(sent[0].contains("Content-Type: text/calendar; charset=utf-8"));
+        assert!(sent[0].contains("BEGIN:VCALENDAR"));
+        assert!(sent[0].contains("Weekly Thoughts Summary"));
+    }
+
+    #[test]
+    fn test_send_email_retries_dropped_connection() {
+        let config = create_test_email_config();
+        let thoughts: Vec<Thought> = vec![];
+        let transport = FlakyEmailTransport {
+            failures_left: Cell::new(2),
+            attempts: Cell::new(0),
+            permanent: false,
+        };
+
+        let result = send_email_with_transport(
+            ThoughtsEmailBody::new(&thoughts),
+            &config,
+            &transport,
+            &test_retry_config(),
+        );
+        assert!(result.is_ok());
+        assert_eq!(transport.attempts.get(), 3);
+    }
+
+    #[test]
+    fn test_send_email_gives_up_after_max_attempts() {
+        let config = create_test_email_config();
+        let thoughts: Vec<Thought> = vec![];
+        let transport = FlakyEmailTransport {
+            failures_left: Cell::new(5),
+            attempts: Cell::new(0),
+            permanent: false,
+        };
+
+        let result = send_email_with_transport(
+            ThoughtsEmailBody::new(&thoughts),
+            &config,
+            &transport,
+            &test_retry_config(),
+        );
+        assert!(matches!(result, Err(AppError::SmtpEmail(_))));
+        assert_eq!(transport.attempts.get(), 3);
+    }
+
+    #[test]
+    fn test_send_email_does_not_retry_permanent_rejection() {
+        let config = create_test_email_config();
+        let thoughts: Vec<Thought> = vec![];
+        let transport = FlakyEmailTransport {
+            failures_left: Cell::new(5),
+            attempts: Cell::new(0),
+            permanent: true,
+        };
+
+        let result = send_email_with_transport(
+            ThoughtsEmailBody::new(&thoughts),
+            &config,
+            &transport,
+            &test_retry_config(),
+        );
+        assert!(matches!(result, Err(AppError::SmtpEmail(_))));
+        assert_eq!(transport.attempts.get(), 1);
+    }
 }
@@ -0,0 +1,149 @@
+use crate::client::Session;
+use crate::db_operations::{get_thought, load_embeddings, missing_embeddings, save_embedding};
+use crate::errors::AppError;
+use crate::reader_config::Config;
+use crate::thought::{EmailSection, Thought};
+use rusqlite::Connection;
+
+/// Cosine similarity of two vectors, 0 when either is all zeros or their
+/// lengths differ.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// The `limit` candidates most similar to `target`, most similar first.
+pub fn nearest<'a>(
+    target: &[f32],
+    candidates: impl Iterator<Item = &'a (i32, Vec<f32>)>,
+    limit: usize,
+) -> Vec<(i32, f32)> {
+    let mut scored: Vec<(i32, f32)> = candidates
+        .map(|(id, vector)| (*id, cosine_similarity(target, vector)))
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(limit);
+    scored
+}
+
+/// Computes and stores embeddings for every thought that has none from the
+/// configured model. Returns the model name the stored vectors belong to.
+pub fn embed_missing(conn: &Connection, config: &Config) -> Result<String, AppError> {
+    let session = Session::new(conn, config.embedding_client_config()?, config.retry());
+    let model = session.config().model();
+    for thought in missing_embeddings(conn, model)? {
+        let vector = session.embedding(thought.content())?;
+        save_embedding(conn, *thought.id(), model, &vector)?;
+    }
+    Ok(model.to_string())
+}
+
+/// The thoughts most similar to thought `id`, with their similarity.
+pub fn similar(
+    conn: &Connection,
+    config: &Config,
+    id: i32,
+    limit: usize,
+) -> Result<Vec<(Thought, f32)>, AppError> {
+    get_thought(conn, id)?.ok_or_else(|| AppError::NotFound(format!("thought #{}", id)))?;
+    let model = embed_missing(conn, config)?;
+    let embeddings = load_embeddings(conn, &model)?;
+    let Some((_, target)) = embeddings.iter().find(|(other, _)| *other == id) else {
+        return Ok(Vec::new());
+    };
+    nearest(
+        target,
+        embeddings.iter().filter(|(other, _)| *other != id),
+        limit,
+    )
+    .into_iter()
+    .filter_map(|(other, similarity)| {
+        get_thought(conn, other)
+            .transpose()
+            .map(|thought| thought.map(|t| (t, similarity)))
+    })
+    .collect()
+}
+
+fn describe(thought: &Thought) -> String {
+    format!(
+        "#{} [{}] {} ({})",
+        thought.id(),
+        thought.thought_type(),
+        thought.content(),
+        thought.created_on()
+    )
+}
+
+/// Digest section linking each new thought to older thoughts above the
+/// similarity threshold.
+pub fn seen_before(
+    conn: &Connection,
+    config: &Config,
+    thoughts: &[Thought],
+) -> Result<EmailSection, AppError> {
+    let settings = config.embeddings();
+    let model = embed_missing(conn, config)?;
+    let embeddings = load_embeddings(conn, &model)?;
+    let mut items = Vec::new();
+    for thought in thoughts {
+        let id = *thought.id();
+        let Some((_, target)) = embeddings.iter().find(|(other, _)| *other == id) else {
+            continue;
+        };
+        let older = embeddings.iter().filter(|(other, _)| *other < id);
+        let matches = nearest(target, older, settings.max_similar())
+            .into_iter()
+            .filter(|(_, similarity)| *similarity >= settings.similarity_threshold())
+            .filter_map(|(other, similarity)| {
+                get_thought(conn, other)
+                    .ok()
+                    .flatten()
+                    .map(|t| format!("{} - {:.0}% similar", describe(&t), similarity * 100.0))
+            })
+            .collect::<Vec<String>>();
+        if !matches.is_empty() {
+            items.push(format!(
+                "{} is like {}",
+                describe(thought),
+                matches.join("; ")
+            ));
+        }
+    }
+    Ok(EmailSection::new("You've had this idea before", items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_nearest_orders_and_limits() {
+        let candidates = [
+            (1, vec![0.0, 1.0]),
+            (2, vec![1.0, 0.1]),
+            (3, vec![1.0, 1.0]),
+        ];
+
+        let result = nearest(&[1.0, 0.0], candidates.iter(), 2);
+
+        assert_eq!(result.iter().map(|(id, _)| *id).collect::<Vec<_>>(), [2, 3]);
+    }
+}
@@ -2,7 +2,7 @@ use clap::Error as ClapError;
 use lettre::address::AddressError;
 use lettre::error::Error as LettreError;
 use reqwest::Error as ReqwestError;
-use std::fmt::{self, write};
+use std::fmt;
 use std::io::Error;
 #[derive(Debug)]
 pub enum AppError {
@@ -12,6 +12,9 @@ pub enum AppError {
     Config(String),
     IO(String),
     Reqwest(String),
+    Json(String),
+    NotFound(String),
+    Truncated(String),
 }
 
 impl fmt::Display for AppError {
@@ -23,6 +26,9 @@ impl fmt::Display for AppError {
             AppError::Config(e) => write!(f, "Config error: {}", e),
             AppError::IO(e) => write!(f, "IO error: {}", e),
             AppError::Reqwest(e) => write!(f, "Reqwest error: {}", e),
+            AppError::Json(e) => write!(f, "JSON error: {}", e),
+            AppError::NotFound(e) => write!(f, "Not found: {}", e),
+            AppError::Truncated(e) => write!(f, "Response truncated: {}", e),
         }
     }
 }
@@ -68,3 +74,9 @@ impl From<ReqwestError> for AppError {
         AppError::Reqwest(value.to_string())
     }
 }
+
+impl From<serde_json::Error> for AppError {
+    fn from(value: serde_json::Error) -> Self {
+        AppError::Json(value.to_string())
+    }
+}
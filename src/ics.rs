@@ -0,0 +1,195 @@
+use crate::thought::Thought;
+use crate::todo::{Priority, TodoStatus};
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, Utc};
+
+const PRODID: &str = "-//thought-app//todos//EN";
+const UID_DOMAIN: &str = "thought-app";
+/// Content lines longer than this many octets are folded (RFC 5545 3.1).
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Escapes commas, semicolons, backslashes and newlines in a TEXT value.
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Splits a content line into CRLF terminated lines of at most 75 octets,
+/// continuation lines starting with a space, without splitting characters.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// A stored `YYYY-MM-DD HH:MM:SS` UTC timestamp as an iCalendar UTC time.
+fn utc_time(stored: &str) -> Option<String> {
+    NaiveDateTime::parse_from_str(stored, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|t| t.format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+fn ics_date(day: NaiveDate) -> String {
+    day.format("%Y%m%d").to_string()
+}
+
+/// iCalendar priority: 1 is highest, 9 lowest.
+fn ics_priority(priority: Priority) -> u8 {
+    match priority {
+        Priority::High => 1,
+        Priority::Medium => 5,
+        Priority::Low => 9,
+    }
+}
+
+fn ics_status(status: TodoStatus) -> &'static str {
+    match status {
+        TodoStatus::Open => "NEEDS-ACTION",
+        TodoStatus::InProgress => "IN-PROCESS",
+        TodoStatus::Done => "COMPLETED",
+    }
+}
+
+/// A VCALENDAR holding a VTODO for every todo in `thoughts`. With `events`,
+/// each dated todo also gets an all-day VEVENT on its due date so it shows
+/// in calendars that ignore tasks. `stamp` is written as every
+/// component's DTSTAMP.
+pub fn calendar(thoughts: &[Thought], stamp: DateTime<Utc>, events: bool) -> String {
+    let stamp = stamp.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for thought in thoughts {
+        let Some(todo) = thought.todo() else {
+            continue;
+        };
+        let summary = format!("SUMMARY:{}", escape_text(thought.content()));
+        let due = todo
+            .due()
+            .and_then(|due| NaiveDate::parse_from_str(due, "%Y-%m-%d").ok());
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:thought-{}@{}", thought.id(), UID_DOMAIN));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        if let Some(created) = utc_time(thought.created_at()) {
+            lines.push(format!("CREATED:{}", created));
+        }
+        lines.push(summary.clone());
+        if let Some(due) = due {
+            lines.push(format!("DUE;VALUE=DATE:{}", ics_date(due)));
+        }
+        if let Some(priority) = todo.priority() {
+            lines.push(format!("PRIORITY:{}", ics_priority(priority)));
+        }
+        lines.push(format!("STATUS:{}", ics_status(todo.status())));
+        if let Some(completed) = todo.completed_at().and_then(utc_time) {
+            lines.push(format!("COMPLETED:{}", completed));
+        }
+        if !thought.tags().is_empty() {
+            let tags: Vec<String> = thought.tags().iter().map(|t| escape_text(t)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        lines.push("END:VTODO".to_string());
+        if let Some(due) = due.filter(|_| events) {
+            lines.push("BEGIN:VEVENT".to_string());
+            lines.push(format!("UID:thought-{}-due@{}", thought.id(), UID_DOMAIN));
+            lines.push(format!("DTSTAMP:{}", stamp));
+            lines.push(format!("DTSTART;VALUE=DATE:{}", ics_date(due)));
+            let end = due.checked_add_days(Days::new(1)).unwrap_or(due);
+            lines.push(format!("DTEND;VALUE=DATE:{}", ics_date(end)));
+            lines.push(summary);
+            lines.push("TRANSP:TRANSPARENT".to_string());
+            lines.push("END:VEVENT".to_string());
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::TodoDetails;
+    use crate::writer_config::ThoughtType;
+
+    fn stamp() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2025-01-15T08:30:00Z")
+            .unwrap()
+            .to_utc()
+    }
+
+    #[test]
+    fn test_calendar_has_vtodo_and_optional_vevent() {
+        let todo = Thought::new(
+            7,
+            ThoughtType::Todo,
+            "Pay rent, then relax".to_string(),
+            false,
+        )
+        .with_created_at("2025-01-02 10:00:00".to_string())
+        .with_tags(vec!["home".to_string()])
+        .with_todo(Some(TodoDetails::new(
+            TodoStatus::InProgress,
+            Some("2025-01-31".to_string()),
+            Some(Priority::High),
+            None,
+        )));
+        let note = Thought::new(8, ThoughtType::Notes, "Not a todo".to_string(), false);
+
+        let ics = calendar(&[todo, note], stamp(), false);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VTODO\r\nEND:VCALENDAR\r\n"));
+        for line in [
+            "UID:thought-7@thought-app",
+            "DTSTAMP:20250115T083000Z",
+            "CREATED:20250102T100000Z",
+            "SUMMARY:Pay rent\\, then relax",
+            "DUE;VALUE=DATE:20250131",
+            "PRIORITY:1",
+            "STATUS:IN-PROCESS",
+            "CATEGORIES:home",
+        ] {
+            assert!(
+                ics.contains(&format!("\r\n{}\r\n", line)),
+                "missing {}",
+                line
+            );
+        }
+        assert!(!ics.contains("Not a todo") && !ics.contains("VEVENT"));
+
+        let ics = calendar(
+            &[
+                Thought::new(1, ThoughtType::Todo, "Due".to_string(), false).with_todo(Some(
+                    TodoDetails::new(TodoStatus::Open, Some("2025-01-31".to_string()), None, None),
+                )),
+            ],
+            stamp(),
+            true,
+        );
+        assert!(ics.contains("\r\nDTSTART;VALUE=DATE:20250131\r\nDTEND;VALUE=DATE:20250201\r\n"));
+    }
+
+    #[test]
+    fn test_fold_splits_long_lines_on_char_boundaries() {
+        let line = format!("SUMMARY:{}", "é".repeat(50));
+
+        let folded = fold(&line);
+
+        assert!(folded.split("\r\n").all(|l| l.len() <= MAX_LINE_OCTETS));
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+}
@@ -1,7 +1,22 @@
+pub mod classify;
 pub mod client;
+pub mod commands;
 pub mod db_operations;
+pub mod digest;
 pub mod email;
+pub mod embeddings;
 pub mod errors;
+pub mod ics;
+pub mod links;
+pub mod prompt;
+pub mod provider;
 pub mod reader_config;
+pub mod retry;
+pub mod review;
+pub mod scoring;
+pub mod subtasks;
+pub mod themes;
 pub mod thought;
+pub mod todo;
+pub mod usage;
 pub mod writer_config;
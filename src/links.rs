@@ -0,0 +1,91 @@
+use clap::ValueEnum;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use std::str::FromStr;
+use strum_macros::{Display, EnumString};
+
+/// How one thought relates to another. `References` links are kept in sync
+/// with the `[[#id]]` references in a thought's content; the others are
+/// added with the `link` command.
+#[derive(Display, EnumString, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[strum(serialize_all = "kebab-case")]
+pub enum LinkKind {
+    RelatesTo,
+    FollowsUp,
+    Blocks,
+    DerivedFrom,
+    #[value(skip)]
+    References,
+}
+
+impl LinkKind {
+    /// The relation read from the linking thought, e.g. "#3 blocks #4".
+    pub fn label(&self) -> &'static str {
+        match self {
+            LinkKind::RelatesTo => "relates to",
+            LinkKind::FollowsUp => "follows up",
+            LinkKind::Blocks => "blocks",
+            LinkKind::DerivedFrom => "derived from",
+            LinkKind::References => "references",
+        }
+    }
+
+    /// The relation read from the linked thought, e.g. "#4 blocked by #3".
+    pub fn backlink_label(&self) -> &'static str {
+        match self {
+            LinkKind::RelatesTo => "relates to",
+            LinkKind::FollowsUp => "followed up by",
+            LinkKind::Blocks => "blocked by",
+            LinkKind::DerivedFrom => "source of",
+            LinkKind::References => "referenced by",
+        }
+    }
+}
+
+impl FromSql for LinkKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        <LinkKind as FromStr>::from_str(value.as_str()?)
+            .map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+/// The ids referenced as `[[#id]]` in `content`, in order of first mention.
+pub fn parse_references(content: &str) -> Vec<i32> {
+    let mut ids = Vec::new();
+    for (i, marker) in content.match_indices("[[#") {
+        let rest = &content[i + marker.len()..];
+        let digits = &rest[..rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len())];
+        if rest[digits.len()..].starts_with("]]")
+            && let Ok(id) = digits.parse()
+            && !ids.contains(&id)
+        {
+            ids.push(id);
+        }
+    }
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_references() {
+        let content =
+            "Builds on [[#12]] and [[#3]], see [[#12]] again. Not [[#x]], [[#4] or [#5]].";
+
+        assert_eq!(parse_references(content), [12, 3]);
+        assert!(parse_references("No links here").is_empty());
+    }
+
+    #[test]
+    fn test_link_kind_names() {
+        assert_eq!(LinkKind::FollowsUp.to_string(), "follows-up");
+        assert_eq!(
+            <LinkKind as FromStr>::from_str("derived-from").unwrap(),
+            LinkKind::DerivedFrom
+        );
+        assert!(<LinkKind as ValueEnum>::from_str("references", true).is_err());
+    }
+}
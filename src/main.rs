@@ -1,40 +1,65 @@
-mod client;
-mod db_operations;
-mod email;
-mod errors;
-mod reader_config;
-mod thought;
-mod writer_config;
-use crate::db_operations::{read, setup_db, write_to_db};
-use crate::reader_config::Args as ReaderConfigArgs;
-use crate::writer_config::{Args as WriterConfigArgs, ThoughtType};
-use clap::Parser;
+#[cfg(any(feature = "writer", feature = "reader"))]
 use std::env;
+#[cfg(any(feature = "writer", feature = "reader"))]
+use thought::errors;
 
+#[cfg(any(feature = "writer", feature = "reader"))]
 fn get_db_path() -> String {
     env::var("DB_PATH").unwrap_or_else(|_| "thought_app.db".to_string())
 }
 #[cfg(feature = "writer")]
 fn main() -> Result<(), errors::AppError> {
+    use clap::Parser;
+    use thought::classify;
+    use thought::client::Session;
+    use thought::db_operations::{save_suggestion, setup_db, write_to_db};
+    use thought::reader_config::Config;
+    use thought::writer_config::Args as WriterConfigArgs;
+
     let args = WriterConfigArgs::try_parse()?;
     let conn = setup_db(&get_db_path())?;
-    write_to_db(&conn, &args)
+    let id = write_to_db(&conn, &args)?;
+    if args.auto_type() {
+        // The thought is already saved, so a failed classification only warns.
+        let suggestion = Config::load(args.config()).and_then(|config| {
+            let session = Session::from_config(&conn, &config);
+            classify::classify(&session, args.content())
+        });
+        match suggestion {
+            Ok(classification) => {
+                save_suggestion(&conn, id, &classification)?;
+                println!(
+                    "Saved #{} as {}; suggested {} (tags: {}), run `accept {}` to apply",
+                    id,
+                    args.thought_type(),
+                    classification.thought_type(),
+                    classification.tags().join(", "),
+                    id
+                );
+            }
+            Err(err) => eprintln!("Saved #{} but could not classify it: {}", id, err),
+        }
+    }
+    Ok(())
 }
 
 #[cfg(feature = "reader")]
 fn main() -> Result<(), errors::AppError> {
+    use clap::Parser;
+    use std::io;
+    use thought::db_operations::setup_db;
+    use thought::reader_config::Args as ReaderConfigArgs;
+    use thought::{commands, digest};
+
     let args = ReaderConfigArgs::try_parse()?;
-    let config = args.config()?;
     let conn = setup_db(&get_db_path())?;
-    let thoughts = read(&conn)?;
-    let content: Vec<&String> = thoughts
-        .iter()
-        .filter(|s| matches!(s.thought_type(), ThoughtType::Project))
-        .map(|s| s.content())
-        .collect();
+    match args.command() {
+        Some(command) => commands::run(command, &args, &conn, &mut io::stdout()),
+        None => digest::run(&conn, &args.config()?),
+    }
+}
 
-    let results = client::get_response(&config.ai_client_config(), &content)?;
-    println!("Cotent {:?}, Result: {}", content, results);
-    email::send_email(&thoughts, &config.email_config())?;
-    Ok(())
+#[cfg(not(any(feature = "writer", feature = "reader")))]
+fn main() {
+    eprintln!("Build with `--features writer` or `--features reader` to run the thought app.");
 }
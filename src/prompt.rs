@@ -0,0 +1,301 @@
+use crate::errors::AppError;
+use crate::reader_config::PromptConfig;
+use crate::thought::Thought;
+use crate::writer_config::ThoughtType;
+use std::fs;
+use std::path::Path;
+
+const PROJECT_PROMPT: &str = include_str!("prompts/project.txt");
+const QUESTION_PROMPT: &str = include_str!("prompts/question.txt");
+const TODO_PROMPT: &str = include_str!("prompts/todo.txt");
+const NOTES_PROMPT: &str = include_str!("prompts/notes.txt");
+const MISC_PROMPT: &str = include_str!("prompts/misc.txt");
+const ANSWER_PROMPT: &str = include_str!("prompts/answer.txt");
+
+/// A prompt with `{{placeholder}}` markers filled in from thoughts.
+///
+/// Supported placeholders:
+/// - `{{thoughts}}` - a numbered list of every thought with its id, tags and date
+/// - `{{content}}` - the thought content
+/// - `{{tags}}` - comma separated tags
+/// - `{{date}}` - the capture date (`YYYY-MM-DD`)
+///
+/// `{{content}}`, `{{tags}}` and `{{date}}` are meant for templates rendered
+/// with a single thought; with several thoughts the values are joined.
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    template: String,
+}
+
+impl PromptTemplate {
+    pub fn new(template: String) -> PromptTemplate {
+        PromptTemplate { template }
+    }
+
+    pub fn builtin(thought_type: &ThoughtType) -> PromptTemplate {
+        let template = match thought_type {
+            ThoughtType::Notes => NOTES_PROMPT,
+            ThoughtType::Project => PROJECT_PROMPT,
+            ThoughtType::Misc => MISC_PROMPT,
+            ThoughtType::Todo => TODO_PROMPT,
+            ThoughtType::Question => QUESTION_PROMPT,
+        };
+        PromptTemplate::new(template.to_string())
+    }
+
+    /// The prompt asking for an answer to a single question.
+    pub fn builtin_answer() -> PromptTemplate {
+        PromptTemplate::new(ANSWER_PROMPT.to_string())
+    }
+
+    pub fn from_file(path: &Path) -> Result<PromptTemplate, AppError> {
+        let template = fs::read_to_string(path)
+            .map_err(|e| AppError::Config(format!("prompt template {}: {}", path.display(), e)))?;
+        Ok(PromptTemplate::new(template))
+    }
+
+    /// The template configured for the thought type, or the built-in default.
+    pub fn for_type(
+        config: &PromptConfig,
+        thought_type: &ThoughtType,
+    ) -> Result<PromptTemplate, AppError> {
+        match config.template_path(thought_type) {
+            Some(path) => Self::from_file(path),
+            None => Ok(Self::builtin(thought_type)),
+        }
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    pub fn render(&self, thoughts: &[&Thought]) -> String {
+        let list = thoughts
+            .iter()
+            .enumerate()
+            .map(|(i, t)| format_thought(i + 1, t))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let content = thoughts
+            .iter()
+            .map(|t| t.content().as_str())
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let tags = thoughts
+            .iter()
+            .flat_map(|t| t.tags().iter().map(String::as_str))
+            .collect::<Vec<&str>>()
+            .join(", ");
+        let dates = thoughts
+            .iter()
+            .map(|t| t.created_on())
+            .collect::<Vec<&str>>()
+            .join(", ");
+
+        let placeholders = [
+            ("{{thoughts}}", list.as_str()),
+            ("{{content}}", content.as_str()),
+            ("{{tags}}", tags.as_str()),
+            ("{{date}}", dates.as_str()),
+        ];
+        substitute(&self.template, &placeholders)
+    }
+
+    /// Splits thoughts, in order, into batches whose rendered prompt is
+    /// estimated at no more than `max_tokens`. A thought too large on its own
+    /// still gets a batch of its own.
+    pub fn batches<'a>(
+        &self,
+        thoughts: &[&'a Thought],
+        max_tokens: usize,
+    ) -> Vec<Vec<&'a Thought>> {
+        let mut batches: Vec<Vec<&Thought>> = Vec::new();
+        let mut current: Vec<&Thought> = Vec::new();
+        for thought in thoughts {
+            current.push(thought);
+            if current.len() > 1 && estimate_tokens(&self.render(&current)) > max_tokens {
+                current.pop();
+                batches.push(std::mem::replace(&mut current, vec![thought]));
+            }
+        }
+        if !current.is_empty() {
+            batches.push(current);
+        }
+        batches
+    }
+}
+
+/// Replaces each placeholder in `template` in one left-to-right pass, so
+/// placeholders inside the inserted values are kept as written.
+fn substitute(template: &str, placeholders: &[(&str, &str)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match placeholders.iter().find(|(name, _)| rest.starts_with(name)) {
+            Some((name, value)) => {
+                rendered.push_str(value);
+                rest = &rest[name.len()..];
+            }
+            None => {
+                rendered.push_str("{{");
+                rest = &rest[2..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Rough token count of `text`, at four characters per token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// One numbered entry, tagged with the thought id so answers can be matched
+/// back to the thought they refer to.
+fn format_thought(number: usize, thought: &Thought) -> String {
+    let mut line = format!("{}. [id: {}] {}", number, thought.id(), thought.content());
+    if !thought.tags().is_empty() {
+        line.push_str(&format!(" (tags: {})", thought.tags().join(", ")));
+    }
+    if !thought.created_on().is_empty() {
+        line.push_str(&format!(" [captured {}]", thought.created_on()));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn tagged_thought() -> Thought {
+        Thought::new(
+            3,
+            ThoughtType::Question,
+            "Why is the sky blue?".to_string(),
+            false,
+        )
+        .with_tags(vec!["physics".to_string(), "light".to_string()])
+        .with_created_at("2025-01-02 10:00:00".to_string())
+    }
+
+    #[test]
+    fn test_builtin_templates_have_thoughts_placeholder() {
+        for thought_type in [
+            ThoughtType::Notes,
+            ThoughtType::Project,
+            ThoughtType::Misc,
+            ThoughtType::Todo,
+            ThoughtType::Question,
+        ] {
+            assert!(
+                PromptTemplate::builtin(&thought_type)
+                    .template()
+                    .contains("{{thoughts}}")
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_thought_list() {
+        let thought = tagged_thought();
+        let template = PromptTemplate::new("Ideas:\n{{thoughts}}".to_string());
+
+        let rendered = template.render(&[&thought]);
+
+        assert_eq!(
+            rendered,
+            "Ideas:\n1. [id: 3] Why is the sky blue? (tags: physics, light) [captured 2025-01-02]"
+        );
+    }
+
+    #[test]
+    fn test_render_numbers_thoughts_with_ids() {
+        let first = Thought::new(12, ThoughtType::Project, "Say \"hi\" ☕".to_string(), false);
+        let second = Thought::new(40, ThoughtType::Project, "Second".to_string(), false);
+        let template = PromptTemplate::new("{{thoughts}}".to_string());
+
+        let rendered = template.render(&[&first, &second]);
+
+        assert_eq!(rendered, "1. [id: 12] Say \"hi\" ☕\n2. [id: 40] Second");
+    }
+
+    #[test]
+    fn test_render_single_thought_placeholders() {
+        let thought = tagged_thought();
+        let template =
+            PromptTemplate::new("Q: {{content}}\nTags: {{tags}}\nAsked: {{date}}".to_string());
+
+        let rendered = template.render(&[&thought]);
+
+        assert_eq!(
+            rendered,
+            "Q: Why is the sky blue?\nTags: physics, light\nAsked: 2025-01-02"
+        );
+    }
+
+    #[test]
+    fn test_render_keeps_placeholders_inside_thought_content() {
+        let thought = Thought::new(
+            5,
+            ThoughtType::Notes,
+            "Template {{date}} and {{tags}} {{".to_string(),
+            false,
+        )
+        .with_created_at("2025-01-02 10:00:00".to_string());
+        let template = PromptTemplate::new("{{content}} ({{date}}) {{unknown}}".to_string());
+
+        let rendered = template.render(&[&thought]);
+
+        assert_eq!(
+            rendered,
+            "Template {{date}} and {{tags}} {{ (2025-01-02) {{unknown}}"
+        );
+    }
+
+    #[test]
+    fn test_for_type_loads_configured_file() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"Custom: {{content}}").unwrap();
+        let config: PromptConfig =
+            toml::from_str(&format!("question = {:?}", file.path().to_str().unwrap())).unwrap();
+
+        let template = PromptTemplate::for_type(&config, &ThoughtType::Question).unwrap();
+        assert_eq!(template.template(), "Custom: {{content}}");
+
+        let fallback = PromptTemplate::for_type(&config, &ThoughtType::Todo).unwrap();
+        assert!(fallback.template().contains("subtasks"));
+    }
+
+    #[test]
+    fn test_for_type_missing_file_is_config_error() {
+        let config: PromptConfig =
+            toml::from_str(r#"project = "/nonexistent/prompt.txt""#).unwrap();
+
+        let result = PromptTemplate::for_type(&config, &ThoughtType::Project);
+        assert!(matches!(result, Err(AppError::Config(_))));
+    }
+
+    #[test]
+    fn test_batches_respect_token_estimate() {
+        let template = PromptTemplate::new("Review:\n{{thoughts}}".to_string());
+        let thoughts: Vec<Thought> = (1..=5)
+            .map(|id| Thought::new(id, ThoughtType::Project, "x".repeat(40), false))
+            .collect();
+        let refs: Vec<&Thought> = thoughts.iter().collect();
+
+        assert_eq!(estimate_tokens("abcde"), 2);
+        let batches = template.batches(&refs, 30);
+        let ids: Vec<Vec<i32>> = batches
+            .iter()
+            .map(|b| b.iter().map(|t| *t.id()).collect())
+            .collect();
+        assert_eq!(ids, [vec![1, 2], vec![3, 4], vec![5]]);
+        assert_eq!(template.batches(&refs, 1).len(), 5);
+        assert_eq!(template.batches(&refs, 10_000).len(), 1);
+    }
+}
@@ -0,0 +1,719 @@
+use crate::errors::AppError;
+use crate::reader_config::{AIClient, AIClientConfig};
+use crate::usage::TokenUsage;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+#[derive(Serialize)]
+struct GeminiPart<'a> {
+    text: &'a str,
+}
+
+#[derive(Serialize)]
+struct GeminiContent<'a> {
+    parts: Vec<GeminiPart<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiGenerationConfig<'a> {
+    temperature: f32,
+    max_output_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_schema: Option<&'a Value>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiRequest<'a> {
+    contents: Vec<GeminiContent<'a>>,
+    generation_config: GeminiGenerationConfig<'a>,
+}
+
+#[derive(Serialize)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+/// Chat completions body, shared by OpenAI and OpenAI compatible servers.
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage<'a>>,
+    temperature: f32,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    /// Asks for token usage in the final streamed chunk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct ClaudeRequest<'a> {
+    model: &'a str,
+    max_tokens: u32,
+    temperature: f32,
+    messages: Vec<ChatMessage<'a>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+#[derive(Deserialize)]
+struct GeminiResponsePart {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct GeminiResponseContent {
+    #[serde(default)]
+    parts: Vec<GeminiResponsePart>,
+}
+
+#[derive(Deserialize)]
+struct GeminiCandidate {
+    content: GeminiResponseContent,
+}
+
+#[derive(Deserialize)]
+struct GeminiResponse {
+    #[serde(default)]
+    candidates: Vec<GeminiCandidate>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatResponseMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    #[serde(default)]
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ClaudeContentBlock {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct ClaudeResponse {
+    #[serde(default)]
+    content: Vec<ClaudeContentBlock>,
+}
+
+#[derive(Serialize)]
+struct GeminiEmbeddingRequest<'a> {
+    model: String,
+    content: GeminiContent<'a>,
+}
+
+/// Embeddings body, shared by OpenAI and OpenAI compatible servers.
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+}
+
+#[derive(Deserialize)]
+struct GeminiEmbedding {
+    values: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct GeminiEmbeddingResponse {
+    embedding: GeminiEmbedding,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    #[serde(default)]
+    data: Vec<EmbeddingData>,
+}
+
+/// OpenAI's strict structured output needs closed objects.
+fn openai_response_format(schema: &Value) -> Value {
+    let mut schema = schema.clone();
+    schema["additionalProperties"] = Value::Bool(false);
+    json!({
+        "type": "json_schema",
+        "json_schema": {"name": "structured_response", "strict": true, "schema": schema}
+    })
+}
+
+/// Serializes the provider specific request body for a single user prompt.
+/// With a schema, providers that support it are asked for JSON conforming to
+/// it; Claude has no such option and relies on the prompt. With `stream`,
+/// the response is requested as server-sent events; Gemini selects streaming
+/// by endpoint instead.
+pub fn request_body(
+    config: &AIClientConfig,
+    prompt: &str,
+    schema: Option<&Value>,
+    stream: bool,
+) -> Result<String, AppError> {
+    let messages = vec![ChatMessage {
+        role: "user",
+        content: prompt,
+    }];
+    let body = match config.ai_client() {
+        AIClient::Gemini => serde_json::to_string(&GeminiRequest {
+            contents: vec![GeminiContent {
+                parts: vec![GeminiPart { text: prompt }],
+            }],
+            generation_config: GeminiGenerationConfig {
+                temperature: config.temperature(),
+                max_output_tokens: config.max_output_tokens(),
+                response_mime_type: schema.map(|_| "application/json"),
+                response_schema: schema,
+            },
+        })?,
+        AIClient::OpenAI | AIClient::Ollama => serde_json::to_string(&ChatRequest {
+            model: config.model(),
+            messages,
+            temperature: config.temperature(),
+            max_tokens: config.max_output_tokens(),
+            response_format: schema.map(openai_response_format),
+            stream,
+            stream_options: stream.then(|| json!({"include_usage": true})),
+        })?,
+        AIClient::Claude => serde_json::to_string(&ClaudeRequest {
+            model: config.model(),
+            max_tokens: config.max_output_tokens(),
+            temperature: config.temperature(),
+            messages,
+            stream,
+        })?,
+    };
+    Ok(body)
+}
+
+/// Extracts the generated text from a provider response body.
+pub fn response_text(ai_client: &AIClient, body: &str) -> Result<String, AppError> {
+    let text = match ai_client {
+        AIClient::Gemini => serde_json::from_str::<GeminiResponse>(body)?
+            .candidates
+            .into_iter()
+            .next()
+            .map(|c| {
+                c.content
+                    .parts
+                    .into_iter()
+                    .map(|p| p.text)
+                    .collect::<String>()
+            }),
+        AIClient::OpenAI | AIClient::Ollama => serde_json::from_str::<ChatResponse>(body)?
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|c| c.message.content),
+        AIClient::Claude => {
+            let blocks = serde_json::from_str::<ClaudeResponse>(body)?.content;
+            (!blocks.is_empty()).then(|| blocks.into_iter().map(|b| b.text).collect())
+        }
+    };
+    text.ok_or_else(|| AppError::Json(format!("no text in {:?} response", ai_client)))
+}
+
+/// Whether the provider stopped generating because it reached the output
+/// token limit rather than finishing the response.
+pub fn truncated(ai_client: &AIClient, body: &str) -> bool {
+    let Ok(body) = serde_json::from_str::<Value>(body) else {
+        return false;
+    };
+    match ai_client {
+        AIClient::Gemini => body["candidates"][0]["finishReason"] == "MAX_TOKENS",
+        AIClient::OpenAI | AIClient::Ollama => body["choices"][0]["finish_reason"] == "length",
+        AIClient::Claude => body["stop_reason"] == "max_tokens",
+    }
+}
+
+/// Token counts reported in a completion or embeddings response, if any.
+pub fn response_usage(ai_client: &AIClient, body: &str) -> Option<TokenUsage> {
+    let body: Value = serde_json::from_str(body).ok()?;
+    let (usage, input, output) = match ai_client {
+        AIClient::Gemini => (
+            &body["usageMetadata"],
+            "promptTokenCount",
+            "candidatesTokenCount",
+        ),
+        AIClient::OpenAI | AIClient::Ollama => {
+            (&body["usage"], "prompt_tokens", "completion_tokens")
+        }
+        AIClient::Claude => (&body["usage"], "input_tokens", "output_tokens"),
+    };
+    let input_tokens = usage[input].as_u64()?;
+    Some(TokenUsage::new(
+        input_tokens,
+        usage[output].as_u64().unwrap_or(0),
+    ))
+}
+
+/// A streamed completion assembled from its server-sent events.
+#[derive(Debug, Default)]
+pub struct StreamedResponse {
+    text: String,
+    stop_reason: Option<String>,
+    usage: Option<TokenUsage>,
+}
+
+impl StreamedResponse {
+    /// Adds one event's `data` payload and returns the text it carried.
+    pub fn push(&mut self, ai_client: &AIClient, data: &str) -> Result<String, AppError> {
+        if data == "[DONE]" {
+            return Ok(String::new());
+        }
+        let event: Value = serde_json::from_str(data)?;
+        let text = match ai_client {
+            AIClient::Gemini => {
+                let candidate = &event["candidates"][0];
+                self.set_stop_reason(&candidate["finishReason"]);
+                self.set_usage(
+                    &event["usageMetadata"],
+                    "promptTokenCount",
+                    "candidatesTokenCount",
+                );
+                candidate["content"]["parts"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|p| p["text"].as_str())
+                    .collect()
+            }
+            AIClient::OpenAI | AIClient::Ollama => {
+                let choice = &event["choices"][0];
+                self.set_stop_reason(&choice["finish_reason"]);
+                self.set_usage(&event["usage"], "prompt_tokens", "completion_tokens");
+                choice["delta"]["content"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string()
+            }
+            AIClient::Claude => match event["type"].as_str() {
+                Some("message_start") => {
+                    self.set_usage(&event["message"]["usage"], "input_tokens", "output_tokens");
+                    String::new()
+                }
+                Some("message_delta") => {
+                    self.set_stop_reason(&event["delta"]["stop_reason"]);
+                    if let Some(output) = event["usage"]["output_tokens"].as_u64() {
+                        let input = self.usage.map_or(0, |u| u.input_tokens());
+                        self.usage = Some(TokenUsage::new(input, output));
+                    }
+                    String::new()
+                }
+                Some("content_block_delta") => event["delta"]["text"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                _ => String::new(),
+            },
+        };
+        self.text.push_str(&text);
+        Ok(text)
+    }
+
+    fn set_stop_reason(&mut self, reason: &Value) {
+        if let Some(reason) = reason.as_str() {
+            self.stop_reason = Some(reason.to_string());
+        }
+    }
+
+    fn set_usage(&mut self, usage: &Value, input: &str, output: &str) {
+        if let Some(input_tokens) = usage[input].as_u64() {
+            let output_tokens = usage[output].as_u64().unwrap_or(0);
+            self.usage = Some(TokenUsage::new(input_tokens, output_tokens));
+        }
+    }
+
+    /// The assembled response in the provider's non-streaming format, so it
+    /// can be read, cached and accounted like any other response body.
+    pub fn into_body(self, ai_client: &AIClient) -> String {
+        let usage = self.usage.unwrap_or_default();
+        let body = match ai_client {
+            AIClient::Gemini => json!({
+                "candidates": [{
+                    "content": {"parts": [{"text": self.text}]},
+                    "finishReason": self.stop_reason
+                }],
+                "usageMetadata": {
+                    "promptTokenCount": usage.input_tokens(),
+                    "candidatesTokenCount": usage.output_tokens()
+                }
+            }),
+            AIClient::OpenAI | AIClient::Ollama => json!({
+                "choices": [{
+                    "message": {"role": "assistant", "content": self.text},
+                    "finish_reason": self.stop_reason
+                }],
+                "usage": {
+                    "prompt_tokens": usage.input_tokens(),
+                    "completion_tokens": usage.output_tokens()
+                }
+            }),
+            AIClient::Claude => json!({
+                "content": [{"type": "text", "text": self.text}],
+                "stop_reason": self.stop_reason,
+                "usage": {
+                    "input_tokens": usage.input_tokens(),
+                    "output_tokens": usage.output_tokens()
+                }
+            }),
+        };
+        body.to_string()
+    }
+}
+
+/// Serializes the provider specific embeddings request for one text.
+pub fn embedding_body(config: &AIClientConfig, text: &str) -> Result<String, AppError> {
+    let body = match config.ai_client() {
+        AIClient::Gemini => serde_json::to_string(&GeminiEmbeddingRequest {
+            model: format!("models/{}", config.model()),
+            content: GeminiContent {
+                parts: vec![GeminiPart { text }],
+            },
+        })?,
+        AIClient::OpenAI | AIClient::Ollama => serde_json::to_string(&EmbeddingRequest {
+            model: config.model(),
+            input: text,
+        })?,
+        AIClient::Claude => {
+            return Err(AppError::Config("Claude has no embeddings API".to_string()));
+        }
+    };
+    Ok(body)
+}
+
+/// Extracts the vector from a provider embeddings response.
+pub fn embedding_vector(ai_client: &AIClient, body: &str) -> Result<Vec<f32>, AppError> {
+    let vector = match ai_client {
+        AIClient::Gemini => Some(
+            serde_json::from_str::<GeminiEmbeddingResponse>(body)?
+                .embedding
+                .values,
+        ),
+        AIClient::OpenAI | AIClient::Ollama => serde_json::from_str::<EmbeddingResponse>(body)?
+            .data
+            .into_iter()
+            .next()
+            .map(|d| d.embedding),
+        AIClient::Claude => None,
+    };
+    vector
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| AppError::Json(format!("no embedding in {:?} response", ai_client)))
+}
+
+/// The JSON inside a response, without the markdown code fence some models
+/// add despite being asked not to.
+pub fn json_payload(text: &str) -> &str {
+    let trimmed = text.trim();
+    trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|t| t.strip_suffix("```"))
+        .unwrap_or(trimmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(ai_client: &str) -> AIClientConfig {
+        serde_json::from_str(&format!(r#"{{"ai_client": "{}"}}"#, ai_client)).unwrap()
+    }
+
+    #[test]
+    fn test_request_body_preserves_unicode_and_quotes() {
+        let prompt = "Idée \"café\" ☕\n\ttabbed \\ slash";
+
+        let body: Value =
+            serde_json::from_str(&request_body(&config("Gemini"), prompt, None, false).unwrap())
+                .unwrap();
+
+        assert_eq!(body["contents"][0]["parts"][0]["text"], prompt);
+    }
+
+    #[test]
+    fn test_request_body_chat_format() {
+        let body: Value =
+            serde_json::from_str(&request_body(&config("OpenAI"), "Hi", None, false).unwrap())
+                .unwrap();
+
+        assert_eq!(body["model"], "gpt-4o-mini");
+        assert_eq!(body["messages"][0]["role"], "user");
+        assert_eq!(body["messages"][0]["content"], "Hi");
+        assert_eq!(body["max_tokens"], 4096);
+    }
+
+    #[test]
+    fn test_request_body_without_schema_omits_json_settings() {
+        let body: Value =
+            serde_json::from_str(&request_body(&config("Gemini"), "Hi", None, false).unwrap())
+                .unwrap();
+        assert!(body["generationConfig"].get("responseSchema").is_none());
+
+        let body: Value =
+            serde_json::from_str(&request_body(&config("OpenAI"), "Hi", None, false).unwrap())
+                .unwrap();
+        assert!(body.get("response_format").is_none());
+    }
+
+    #[test]
+    fn test_request_body_with_schema() {
+        let schema = json!({"type": "object", "properties": {"a": {"type": "string"}}});
+
+        let body: Value = serde_json::from_str(
+            &request_body(&config("Gemini"), "Hi", Some(&schema), false).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            body["generationConfig"]["responseMimeType"],
+            "application/json"
+        );
+        assert_eq!(body["generationConfig"]["responseSchema"], schema);
+
+        let body: Value = serde_json::from_str(
+            &request_body(&config("Ollama"), "Hi", Some(&schema), false).unwrap(),
+        )
+        .unwrap();
+        let format = &body["response_format"];
+        assert_eq!(format["type"], "json_schema");
+        assert_eq!(format["json_schema"]["strict"], true);
+        assert_eq!(
+            format["json_schema"]["schema"]["additionalProperties"],
+            false
+        );
+
+        let body: Value = serde_json::from_str(
+            &request_body(&config("Claude"), "Hi", Some(&schema), false).unwrap(),
+        )
+        .unwrap();
+        assert!(body.get("response_format").is_none());
+    }
+
+    #[test]
+    fn test_response_text_gemini() {
+        let body = r#"{"candidates":[{"content":{"parts":[{"text":"Hello "},{"text":"there"}]}}]}"#;
+        assert_eq!(
+            response_text(&AIClient::Gemini, body).unwrap(),
+            "Hello there"
+        );
+    }
+
+    #[test]
+    fn test_response_text_openai() {
+        let body = r#"{"choices":[{"message":{"role":"assistant","content":"Answer"}}]}"#;
+        assert_eq!(response_text(&AIClient::OpenAI, body).unwrap(), "Answer");
+    }
+
+    #[test]
+    fn test_response_text_claude() {
+        let body = r#"{"content":[{"type":"text","text":"Claude says hi"}]}"#;
+        assert_eq!(
+            response_text(&AIClient::Claude, body).unwrap(),
+            "Claude says hi"
+        );
+    }
+
+    #[test]
+    fn test_response_text_missing_content_is_error() {
+        let result = response_text(&AIClient::Gemini, r#"{"candidates":[]}"#);
+        assert!(matches!(result, Err(AppError::Json(_))));
+
+        let result = response_text(&AIClient::OpenAI, "not json");
+        assert!(matches!(result, Err(AppError::Json(_))));
+    }
+
+    #[test]
+    fn test_json_payload_strips_code_fence() {
+        assert_eq!(
+            json_payload("```json\n{\"a\": 1}\n```").trim(),
+            r#"{"a": 1}"#
+        );
+        assert_eq!(json_payload("```\n[]\n```").trim(), "[]");
+        assert_eq!(json_payload("  {}  "), "{}");
+    }
+
+    #[test]
+    fn test_embedding_body_per_provider() {
+        let body: Value =
+            serde_json::from_str(&embedding_body(&config("Ollama"), "Idea").unwrap()).unwrap();
+        assert_eq!(body["input"], "Idea");
+        assert_eq!(body["model"], "llama3.2");
+
+        let body: Value =
+            serde_json::from_str(&embedding_body(&config("Gemini"), "Idea").unwrap()).unwrap();
+        assert_eq!(body["model"], "models/gemini-2.0-flash");
+        assert_eq!(body["content"]["parts"][0]["text"], "Idea");
+
+        assert!(matches!(
+            embedding_body(&config("Claude"), "Idea"),
+            Err(AppError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_embedding_vector_per_provider() {
+        let body = r#"{"data":[{"object":"embedding","embedding":[0.1,0.2]}]}"#;
+        assert_eq!(
+            embedding_vector(&AIClient::OpenAI, body).unwrap(),
+            [0.1, 0.2]
+        );
+
+        let body = r#"{"embedding":{"values":[0.5]}}"#;
+        assert_eq!(embedding_vector(&AIClient::Gemini, body).unwrap(), [0.5]);
+
+        let result = embedding_vector(&AIClient::Ollama, r#"{"data":[]}"#);
+        assert!(matches!(result, Err(AppError::Json(_))));
+    }
+
+    #[test]
+    fn test_response_usage_per_provider() {
+        let body = r#"{"candidates":[],"usageMetadata":{"promptTokenCount":12,"candidatesTokenCount":30}}"#;
+        assert_eq!(
+            response_usage(&AIClient::Gemini, body),
+            Some(TokenUsage::new(12, 30))
+        );
+
+        let body = r#"{"choices":[],"usage":{"prompt_tokens":5,"completion_tokens":7}}"#;
+        assert_eq!(
+            response_usage(&AIClient::OpenAI, body),
+            Some(TokenUsage::new(5, 7))
+        );
+
+        let body = r#"{"content":[],"usage":{"input_tokens":3,"output_tokens":4}}"#;
+        assert_eq!(
+            response_usage(&AIClient::Claude, body),
+            Some(TokenUsage::new(3, 4))
+        );
+
+        let body = r#"{"data":[],"usage":{"prompt_tokens":8,"total_tokens":8}}"#;
+        assert_eq!(
+            response_usage(&AIClient::Ollama, body),
+            Some(TokenUsage::new(8, 0))
+        );
+
+        assert_eq!(response_usage(&AIClient::OpenAI, r#"{"choices":[]}"#), None);
+    }
+
+    #[test]
+    fn test_truncated_reads_finish_reason() {
+        let gemini = r#"{"candidates":[{"content":{"parts":[]},"finishReason":"MAX_TOKENS"}]}"#;
+        assert!(truncated(&AIClient::Gemini, gemini));
+        assert!(!truncated(
+            &AIClient::Gemini,
+            &gemini.replace("MAX_TOKENS", "STOP")
+        ));
+
+        let chat = r#"{"choices":[{"message":{"content":"a"},"finish_reason":"length"}]}"#;
+        assert!(truncated(&AIClient::OpenAI, chat));
+        assert!(truncated(&AIClient::Ollama, chat));
+        assert!(!truncated(
+            &AIClient::OpenAI,
+            &chat.replace("length", "stop")
+        ));
+
+        assert!(truncated(
+            &AIClient::Claude,
+            r#"{"content":[],"stop_reason":"max_tokens"}"#
+        ));
+        assert!(!truncated(
+            &AIClient::Claude,
+            r#"{"content":[],"stop_reason":"end_turn"}"#
+        ));
+        assert!(!truncated(&AIClient::OpenAI, "not json"));
+    }
+
+    #[test]
+    fn test_request_body_streaming_flags() {
+        let body: Value =
+            serde_json::from_str(&request_body(&config("OpenAI"), "Hi", None, true).unwrap())
+                .unwrap();
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["stream_options"]["include_usage"], true);
+
+        let body: Value =
+            serde_json::from_str(&request_body(&config("Claude"), "Hi", None, true).unwrap())
+                .unwrap();
+        assert_eq!(body["stream"], true);
+
+        let body: Value =
+            serde_json::from_str(&request_body(&config("Claude"), "Hi", None, false).unwrap())
+                .unwrap();
+        assert!(body.get("stream").is_none());
+    }
+
+    #[test]
+    fn test_streamed_response_assembles_body() {
+        let mut stream = StreamedResponse::default();
+        for data in [
+            r#"{"choices":[{"delta":{"role":"assistant","content":"Hel"}}]}"#,
+            r#"{"choices":[{"delta":{"content":"lo"},"finish_reason":"length"}]}"#,
+            r#"{"choices":[],"usage":{"prompt_tokens":4,"completion_tokens":2}}"#,
+            "[DONE]",
+        ] {
+            stream.push(&AIClient::OpenAI, data).unwrap();
+        }
+        let body = stream.into_body(&AIClient::OpenAI);
+        assert_eq!(response_text(&AIClient::OpenAI, &body).unwrap(), "Hello");
+        assert!(truncated(&AIClient::OpenAI, &body));
+        assert_eq!(
+            response_usage(&AIClient::OpenAI, &body),
+            Some(TokenUsage::new(4, 2))
+        );
+
+        let mut stream = StreamedResponse::default();
+        let events = [
+            r#"{"type":"message_start","message":{"usage":{"input_tokens":9,"output_tokens":1}}}"#,
+            r#"{"type":"content_block_delta","delta":{"type":"text_delta","text":"Hi "}}"#,
+            r#"{"type":"content_block_delta","delta":{"type":"text_delta","text":"there"}}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":3}}"#,
+        ];
+        let texts: Vec<String> = events
+            .iter()
+            .map(|data| stream.push(&AIClient::Claude, data).unwrap())
+            .collect();
+        assert_eq!(texts, ["", "Hi ", "there", ""]);
+        let body = stream.into_body(&AIClient::Claude);
+        assert_eq!(response_text(&AIClient::Claude, &body).unwrap(), "Hi there");
+        assert!(!truncated(&AIClient::Claude, &body));
+        assert_eq!(
+            response_usage(&AIClient::Claude, &body),
+            Some(TokenUsage::new(9, 3))
+        );
+
+        let mut stream = StreamedResponse::default();
+        stream
+            .push(
+                &AIClient::Gemini,
+                r#"{"candidates":[{"content":{"parts":[{"text":"Idea"}]},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":5,"candidatesTokenCount":1}}"#,
+            )
+            .unwrap();
+        let body = stream.into_body(&AIClient::Gemini);
+        assert_eq!(response_text(&AIClient::Gemini, &body).unwrap(), "Idea");
+        assert_eq!(
+            response_usage(&AIClient::Gemini, &body),
+            Some(TokenUsage::new(5, 1))
+        );
+    }
+}
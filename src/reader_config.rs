@@ -1,29 +1,377 @@
 use crate::errors::AppError;
-use clap::Parser;
+use crate::links::LinkKind;
+use crate::thought::ThoughtState;
+use crate::todo::{Priority, TodoStatus, parse_due_arg};
+use crate::usage::TokenUsage;
+use crate::writer_config::ThoughtType;
+use clap::{Parser, Subcommand, ValueEnum};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 pub struct Args {
     #[arg(short, long, default_value = "config.toml")]
     config: PathBuf,
-    #[arg(short, long, default_value_t = true)]
+    /// Stream AI responses to the terminal as they are generated
+    #[arg(short, long)]
     verbose: bool,
+    /// Send every prompt to the AI provider, ignoring cached responses
+    #[arg(long)]
+    no_cache: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
+
+/// Commands for working with stored thoughts. Without a command the reader
+/// runs the weekly digest.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Show a single thought with its stored analysis, links and backlinks
+    Show { id: i32 },
+    /// Change a thought's content or type. Without flags the content opens
+    /// in $EDITOR
+    Edit {
+        id: i32,
+        /// Replace the content instead of opening an editor
+        #[arg(long)]
+        content: Option<String>,
+        #[arg(long, visible_alias = "type", value_enum)]
+        thought_type: Option<ThoughtType>,
+    },
+    /// Move thoughts out of the digest and listings without trashing them
+    Archive {
+        #[arg(required = true)]
+        ids: Vec<i32>,
+    },
+    /// Move thoughts to the trash, from where they can be restored until the
+    /// trash is emptied
+    #[command(visible_alias = "delete")]
+    Trash {
+        #[arg(required = true)]
+        ids: Vec<i32>,
+    },
+    /// Make archived or trashed thoughts active again
+    Restore {
+        #[arg(required = true)]
+        ids: Vec<i32>,
+    },
+    /// Permanently delete trashed thoughts, keeping their last version in the
+    /// revision history
+    EmptyTrash,
+    /// List stored thoughts, newest first
+    List {
+        #[command(flatten)]
+        filter: ThoughtFilter,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Ask the AI provider to suggest a type and tags for stored thoughts
+    Reclassify {
+        /// Only classify thoughts currently filed under this type
+        #[arg(long, value_enum, default_value_t = ThoughtType::Misc)]
+        thought_type: ThoughtType,
+        /// Maximum number of thoughts to classify in one run
+        #[arg(long, default_value_t = 20)]
+        limit: u32,
+    },
+    /// Put reviewed thoughts back into the next digest, by id or by filter
+    Unreview {
+        #[arg(required_unless_present_any = [
+            "thought_type", "tag", "state", "since", "until", "search", "tree", "pinned", "starred",
+        ])]
+        ids: Vec<i32>,
+        #[command(flatten)]
+        filter: ThoughtFilter,
+    },
+    /// Mark todos done
+    Done {
+        #[arg(required = true)]
+        ids: Vec<i32>,
+    },
+    /// List todos or change their status, due date and priority
+    Todo {
+        #[command(subcommand)]
+        command: TodoCommand,
+    },
+    /// Link one thought to another, e.g. a todo derived from a project
+    Link {
+        source: i32,
+        target: i32,
+        /// How the source relates to the target, default relates-to
+        #[arg(long, value_enum)]
+        kind: Option<LinkKind>,
+        /// Remove the link instead, of every kind unless --kind is given
+        #[arg(long)]
+        remove: bool,
+    },
+    /// File a thought under a parent, e.g. a todo under its project, or
+    /// without a parent make it top-level again
+    Parent { id: i32, parent: Option<i32> },
+    /// Add todos under a thought, one per next step of its idea score or,
+    /// without a score, as suggested by the AI provider
+    Subtasks {
+        id: i32,
+        /// Ask the AI provider even when the thought has a score
+        #[arg(long)]
+        ai: bool,
+    },
+    /// Pin thoughts to the top of every digest, or unpin pinned ones
+    Pin {
+        #[arg(required = true)]
+        ids: Vec<i32>,
+    },
+    /// Star thoughts, or unstar starred ones
+    Star {
+        #[arg(required = true)]
+        ids: Vec<i32>,
+    },
+    /// Export stored thoughts for other tools
+    Export {
+        #[command(subcommand)]
+        format: ExportFormat,
+    },
+    /// Rate how well a thought held up on review, from 0 (forgotten) to 5
+    /// (perfect), scheduling its next review
+    Rate {
+        id: i32,
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=5))]
+        quality: u8,
+    },
+    /// List suggestions awaiting accept or reject
+    Suggestions,
+    /// List questions that have not been marked answered
+    Questions,
+    /// Mark questions as answered so they leave the open questions list
+    Answered {
+        #[arg(required = true)]
+        ids: Vec<i32>,
+        /// Put the questions back on the open list instead
+        #[arg(long)]
+        reopen: bool,
+    },
+    /// Report AI calls, tokens and estimated cost
+    Usage {
+        /// Month to report as YYYY-MM, default the current month
+        #[arg(long)]
+        month: Option<String>,
+        /// Report all recorded usage instead of one month
+        #[arg(long, conflicts_with = "month")]
+        all: bool,
+    },
+    /// List the thoughts most similar to a thought, using embeddings
+    Similar {
+        id: i32,
+        #[arg(long, default_value_t = 5)]
+        limit: usize,
+    },
+    /// Apply the suggested type and tags to the given thoughts
+    Accept {
+        #[arg(required = true)]
+        ids: Vec<i32>,
+    },
+    /// Dismiss the suggestions for the given thoughts
+    Reject {
+        #[arg(required = true)]
+        ids: Vec<i32>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TodoCommand {
+    /// List unfinished todos, soonest due first
+    List {
+        /// Only todos due before today
+        #[arg(long)]
+        overdue: bool,
+        /// Only todos with this status instead of every unfinished one
+        #[arg(long, value_enum)]
+        status: Option<TodoStatus>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Change a todo's status, due date or priority
+    Set {
+        id: i32,
+        #[arg(long, value_enum)]
+        status: Option<TodoStatus>,
+        /// YYYY-MM-DD, tomorrow, friday, next week, in 3 days
+        #[arg(long, value_parser = parse_due_arg)]
+        due: Option<String>,
+        /// Remove the due date
+        #[arg(long, conflicts_with = "due")]
+        no_due: bool,
+        #[arg(long, value_enum)]
+        priority: Option<Priority>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExportFormat {
+    /// Unfinished todos as an iCalendar file of VTODOs
+    Ics {
+        /// Also add an all-day event on each due date
+        #[arg(long)]
+        events: bool,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Which stored thoughts `list` shows, in what order and which page.
+#[derive(clap::Args, Debug)]
+pub struct ThoughtFilter {
+    /// Only thoughts of this type
+    #[arg(long, value_enum)]
+    thought_type: Option<ThoughtType>,
+    /// Only thoughts already sent in a digest
+    #[arg(long, conflicts_with = "unreviewed")]
+    reviewed: bool,
+    /// Only thoughts not yet sent in a digest
+    #[arg(long)]
+    unreviewed: bool,
+    /// Only thoughts with this tag
+    #[arg(long)]
+    tag: Option<String>,
+    /// Only thoughts in this state
+    #[arg(long, value_enum, default_value_t = ThoughtState::Active)]
+    state: ThoughtState,
+    /// Only thoughts captured on or after this date (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_date)]
+    since: Option<String>,
+    /// Only thoughts captured on or before this date (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_date)]
+    until: Option<String>,
+    /// Only thoughts containing this text
+    #[arg(long)]
+    search: Option<String>,
+    /// Only top-level thoughts, each listed with its sub-thoughts under it
+    #[arg(long)]
+    tree: bool,
+    /// Only pinned thoughts
+    #[arg(long)]
+    pinned: bool,
+    /// Only starred thoughts
+    #[arg(long)]
+    starred: bool,
+    #[arg(long, value_enum, default_value_t = SortBy::Date)]
+    sort: SortBy,
+    /// Oldest first instead of newest first
+    #[arg(long)]
+    asc: bool,
+    /// Thoughts per page
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u32).range(1..))]
+    limit: u32,
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    page: u32,
+}
+
+impl ThoughtFilter {
+    pub fn thought_type(&self) -> Option<&ThoughtType> {
+        self.thought_type.as_ref()
+    }
+    /// The reviewed state to match, or None for both.
+    pub fn reviewed(&self) -> Option<bool> {
+        match (self.reviewed, self.unreviewed) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+    pub fn state(&self) -> ThoughtState {
+        self.state
+    }
+    pub fn since(&self) -> Option<&str> {
+        self.since.as_deref()
+    }
+    pub fn until(&self) -> Option<&str> {
+        self.until.as_deref()
+    }
+    pub fn search(&self) -> Option<&str> {
+        self.search.as_deref()
+    }
+    pub fn tree(&self) -> bool {
+        self.tree
+    }
+    pub fn pinned(&self) -> bool {
+        self.pinned
+    }
+    pub fn starred(&self) -> bool {
+        self.starred
+    }
+    pub fn sort(&self) -> SortBy {
+        self.sort
+    }
+    pub fn asc(&self) -> bool {
+        self.asc
+    }
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+    pub fn page(&self) -> u32 {
+        self.page
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SortBy {
+    /// Capture date
+    Date,
+    Id,
+}
+
+/// How commands print lists of thoughts.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns for reading
+    Table,
+    /// One JSON array
+    Json,
+    /// One JSON object per line, for piping into other tools
+    Ndjson,
+}
+
+fn parse_date(value: &str) -> Result<String, String> {
+    let bytes = value.as_bytes();
+    let valid = bytes.len() == 10
+        && bytes.iter().enumerate().all(|(i, b)| match i {
+            4 | 7 => *b == b'-',
+            _ => b.is_ascii_digit(),
+        });
+    if valid {
+        Ok(value.to_string())
+    } else {
+        Err(format!("expected a date as YYYY-MM-DD, got {}", value))
+    }
+}
+
 impl Args {
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+
     pub fn parse_config(contents: &str) -> Result<Config, AppError> {
         let config: Config = toml::from_str(contents)?;
         Ok(config)
     }
 
-    fn load_config(path: &PathBuf) -> Result<Config, AppError> {
+    fn load_config(path: &Path) -> Result<Config, AppError> {
         let contents = fs::read_to_string(path)?;
         Self::parse_config(&contents)
     }
 
     pub fn config(&self) -> Result<Config, AppError> {
-        Self::load_config(&self.config)
+        let mut config = Self::load_config(&self.config)?;
+        if self.no_cache {
+            config.cache.enabled = false;
+        }
+        config.verbose = self.verbose;
+        Ok(config)
     }
 }
 
@@ -54,7 +402,14 @@ impl EmailConfig {
     }
 }
 pub trait AIClientDetails {
-    fn endpoint(&self) -> &str;
+    fn base_url(&self) -> &str;
+    fn default_model(&self) -> &str;
+    fn endpoint(&self, base_url: &str, model: &str) -> String;
+    /// The endpoint answering with server-sent events.
+    fn stream_endpoint(&self, base_url: &str, model: &str) -> String;
+    /// None for providers without an embeddings API.
+    fn default_embedding_model(&self) -> Option<&str>;
+    fn embedding_endpoint(&self, base_url: &str, model: &str) -> Option<String>;
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -62,23 +417,93 @@ pub enum AIClient {
     OpenAI,
     Claude,
     Gemini,
+    /// A local Ollama server, or any other server exposing the OpenAI
+    /// compatible chat completions API.
+    Ollama,
 }
 impl AIClientDetails for AIClient {
-    fn endpoint(&self) -> &str {
+    fn base_url(&self) -> &str {
+        match self {
+            AIClient::OpenAI => "https://api.openai.com",
+            AIClient::Gemini => "https://generativelanguage.googleapis.com",
+            AIClient::Claude => "https://api.anthropic.com",
+            AIClient::Ollama => "http://localhost:11434",
+        }
+    }
+
+    fn default_model(&self) -> &str {
+        match self {
+            AIClient::OpenAI => "gpt-4o-mini",
+            AIClient::Gemini => "gemini-2.0-flash",
+            AIClient::Claude => "claude-3-5-haiku-latest",
+            AIClient::Ollama => "llama3.2",
+        }
+    }
+
+    fn endpoint(&self, base_url: &str, model: &str) -> String {
+        let base_url = base_url.trim_end_matches('/');
+        match self {
+            AIClient::OpenAI | AIClient::Ollama => format!("{}/v1/chat/completions", base_url),
+            AIClient::Gemini => format!("{}/v1/models/{}:generateContent", base_url, model),
+            AIClient::Claude => format!("{}/v1/messages", base_url),
+        }
+    }
+
+    fn stream_endpoint(&self, base_url: &str, model: &str) -> String {
+        match self {
+            AIClient::Gemini => format!(
+                "{}/v1/models/{}:streamGenerateContent?alt=sse",
+                base_url.trim_end_matches('/'),
+                model
+            ),
+            _ => self.endpoint(base_url, model),
+        }
+    }
+
+    fn default_embedding_model(&self) -> Option<&str> {
         match self {
-            AIClient::OpenAI => "https://api.openai.com/v1/chat/completions",
-            AIClient::Gemini => {
-                "https://generativelanguage.googleapis.com/v1/models/gemini-2.0-flash:generateContent"
-            }
-            AIClient::Claude => "https://api.anthropic.com/v1/messages",
+            AIClient::OpenAI => Some("text-embedding-3-small"),
+            AIClient::Gemini => Some("text-embedding-004"),
+            AIClient::Ollama => Some("nomic-embed-text"),
+            AIClient::Claude => None,
         }
     }
+
+    fn embedding_endpoint(&self, base_url: &str, model: &str) -> Option<String> {
+        let base_url = base_url.trim_end_matches('/');
+        match self {
+            AIClient::OpenAI | AIClient::Ollama => Some(format!("{}/v1/embeddings", base_url)),
+            AIClient::Gemini => Some(format!("{}/v1/models/{}:embedContent", base_url, model)),
+            AIClient::Claude => None,
+        }
+    }
+}
+
+fn default_temperature() -> f32 {
+    0.7
+}
+
+fn default_max_output_tokens() -> u32 {
+    4096
+}
+
+fn default_timeout_secs() -> u64 {
+    120
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AIClientConfig {
+    #[serde(default)]
     bearer_token: String,
     ai_client: AIClient,
+    model: Option<String>,
+    #[serde(default = "default_temperature")]
+    temperature: f32,
+    #[serde(default = "default_max_output_tokens")]
+    max_output_tokens: u32,
+    #[serde(default = "default_timeout_secs")]
+    timeout_secs: u64,
+    base_url: Option<String>,
 }
 
 impl Clone for AIClient {
@@ -87,6 +512,7 @@ impl Clone for AIClient {
             AIClient::Claude => AIClient::Claude,
             AIClient::OpenAI => AIClient::OpenAI,
             AIClient::Gemini => AIClient::Gemini,
+            AIClient::Ollama => AIClient::Ollama,
         }
     }
 }
@@ -98,30 +524,587 @@ impl AIClientConfig {
     pub fn ai_client(&self) -> &AIClient {
         &self.ai_client
     }
+    /// The configured model, falling back to the provider's default.
+    pub fn model(&self) -> &str {
+        self.model
+            .as_deref()
+            .unwrap_or_else(|| self.ai_client.default_model())
+    }
+    pub fn temperature(&self) -> f32 {
+        self.temperature
+    }
+    pub fn max_output_tokens(&self) -> u32 {
+        self.max_output_tokens
+    }
+    pub fn timeout_secs(&self) -> u64 {
+        self.timeout_secs
+    }
+    /// The configured base URL, falling back to the provider's public API.
+    pub fn base_url(&self) -> &str {
+        self.base_url
+            .as_deref()
+            .unwrap_or_else(|| self.ai_client.base_url())
+    }
+    pub fn endpoint(&self) -> String {
+        self.ai_client.endpoint(self.base_url(), self.model())
+    }
+    pub fn stream_endpoint(&self) -> String {
+        self.ai_client
+            .stream_endpoint(self.base_url(), self.model())
+    }
+    /// The embeddings URL for the configured model, which should be an
+    /// embedding model.
+    pub fn embedding_endpoint(&self) -> Result<String, AppError> {
+        self.ai_client
+            .embedding_endpoint(self.base_url(), self.model())
+            .ok_or_else(|| AppError::Config(format!("{:?} has no embeddings API", self.ai_client)))
+    }
+}
+
+/// Whether analysed thoughts are sent together in batched prompts, one
+/// prompt per thought so each thought gets its own stored analysis, or one
+/// prompt per thought asking for a structured
+/// [`IdeaScore`](crate::scoring::IdeaScore).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalysisMode {
+    #[default]
+    Batch,
+    PerThought,
+    Structured,
+}
+
+fn default_analysed_types() -> Vec<ThoughtType> {
+    vec![ThoughtType::Project]
+}
+
+fn default_max_batch_tokens() -> usize {
+    8000
+}
+
+/// Which thought types are sent for AI analysis, and optional prompt template
+/// files overriding the built-in prompt for each type.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PromptConfig {
+    #[serde(default = "default_analysed_types")]
+    analyse: Vec<ThoughtType>,
+    #[serde(default)]
+    mode: AnalysisMode,
+    /// Estimated prompt tokens per request in batch mode. Thoughts that do
+    /// not fit are sent in further requests.
+    #[serde(default = "default_max_batch_tokens")]
+    max_batch_tokens: usize,
+    notes: Option<PathBuf>,
+    project: Option<PathBuf>,
+    misc: Option<PathBuf>,
+    todo: Option<PathBuf>,
+    question: Option<PathBuf>,
+}
+
+impl Default for PromptConfig {
+    fn default() -> Self {
+        PromptConfig {
+            analyse: default_analysed_types(),
+            mode: AnalysisMode::default(),
+            max_batch_tokens: default_max_batch_tokens(),
+            notes: None,
+            project: None,
+            misc: None,
+            todo: None,
+            question: None,
+        }
+    }
+}
+
+impl PromptConfig {
+    pub fn analyse(&self) -> &[ThoughtType] {
+        &self.analyse
+    }
+    pub fn mode(&self) -> AnalysisMode {
+        self.mode
+    }
+    pub fn max_batch_tokens(&self) -> usize {
+        self.max_batch_tokens
+    }
+    pub fn template_path(&self, thought_type: &ThoughtType) -> Option<&PathBuf> {
+        match thought_type {
+            ThoughtType::Notes => self.notes.as_ref(),
+            ThoughtType::Project => self.project.as_ref(),
+            ThoughtType::Misc => self.misc.as_ref(),
+            ThoughtType::Todo => self.todo.as_ref(),
+            ThoughtType::Question => self.question.as_ref(),
+        }
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30_000
+}
+
+fn default_jitter() -> bool {
+    true
+}
+
+/// Retry policy for AI provider calls and sending the digest email.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryConfig {
+    #[serde(default = "default_max_attempts")]
+    max_attempts: u32,
+    #[serde(default = "default_initial_backoff_ms")]
+    initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    max_backoff_ms: u64,
+    #[serde(default = "default_jitter")]
+    jitter: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: default_max_attempts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            jitter: default_jitter(),
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+    pub fn initial_backoff_ms(&self) -> u64 {
+        self.initial_backoff_ms
+    }
+    pub fn max_backoff_ms(&self) -> u64 {
+        self.max_backoff_ms
+    }
+    pub fn jitter(&self) -> bool {
+        self.jitter
+    }
+}
+
+fn default_sort_by_score() -> bool {
+    true
+}
+
+fn default_theme_threshold() -> f32 {
+    0.75
+}
+
+/// How the digest email presents thoughts.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DigestConfig {
+    /// List scored project ideas first, highest novelty first.
+    #[serde(default = "default_sort_by_score")]
+    sort_by_score: bool,
+    /// Leave scored project ideas below this novelty score out of the email.
+    min_novelty_score: Option<u8>,
+    /// Cluster the week's thoughts by embedding and list them under AI named
+    /// themes.
+    #[serde(default)]
+    group_by_theme: bool,
+    /// Cosine similarity to a theme's centroid needed to join it.
+    #[serde(default = "default_theme_threshold")]
+    theme_threshold: f32,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        DigestConfig {
+            sort_by_score: default_sort_by_score(),
+            min_novelty_score: None,
+            group_by_theme: false,
+            theme_threshold: default_theme_threshold(),
+        }
+    }
+}
+
+impl DigestConfig {
+    pub fn sort_by_score(&self) -> bool {
+        self.sort_by_score
+    }
+    pub fn min_novelty_score(&self) -> Option<u8> {
+        self.min_novelty_score
+    }
+    pub fn group_by_theme(&self) -> bool {
+        self.group_by_theme
+    }
+    pub fn theme_threshold(&self) -> f32 {
+        self.theme_threshold
+    }
+}
+
+/// AI answers for `Question` thoughts. Unanswered questions stay in the
+/// digest's open questions list until marked answered.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct QuestionConfig {
+    #[serde(default)]
+    answer: bool,
+    /// Template replacing the built-in answer prompt.
+    prompt: Option<PathBuf>,
+}
+
+impl QuestionConfig {
+    pub fn answer(&self) -> bool {
+        self.answer
+    }
+    pub fn prompt(&self) -> Option<&PathBuf> {
+        self.prompt.as_ref()
+    }
+}
+
+/// Price of a model in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ModelPrice {
+    input: f64,
+    output: f64,
+}
+
+/// Prices used to estimate AI spend, and an optional monthly budget that
+/// stops the digest making AI calls once reached.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UsageConfig {
+    monthly_budget: Option<f64>,
+    /// Keyed by model name.
+    #[serde(default)]
+    prices: HashMap<String, ModelPrice>,
+}
+
+impl UsageConfig {
+    pub fn monthly_budget(&self) -> Option<f64> {
+        self.monthly_budget
+    }
+    /// Estimated cost in US dollars, None when the model has no price.
+    pub fn cost(&self, model: &str, tokens: &TokenUsage) -> Option<f64> {
+        self.prices.get(model).map(|price| {
+            (tokens.input_tokens() as f64 * price.input
+                + tokens.output_tokens() as f64 * price.output)
+                / 1_000_000.0
+        })
+    }
+}
+
+fn default_similarity_threshold() -> f32 {
+    0.85
+}
+
+fn default_max_similar() -> usize {
+    3
+}
+
+/// Embedding vectors for finding thoughts similar to earlier ones. Uses the
+/// main AI provider unless another one, e.g. a local Ollama server, is set.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmbeddingConfig {
+    #[serde(default)]
+    enabled: bool,
+    ai_client: Option<AIClient>,
+    model: Option<String>,
+    base_url: Option<String>,
+    bearer_token: Option<String>,
+    /// Cosine similarity at which an older thought counts as the same idea.
+    #[serde(default = "default_similarity_threshold")]
+    similarity_threshold: f32,
+    /// Older thoughts listed per new thought in the digest.
+    #[serde(default = "default_max_similar")]
+    max_similar: usize,
+}
+
+impl Default for EmbeddingConfig {
+    fn default() -> Self {
+        EmbeddingConfig {
+            enabled: false,
+            ai_client: None,
+            model: None,
+            base_url: None,
+            bearer_token: None,
+            similarity_threshold: default_similarity_threshold(),
+            max_similar: default_max_similar(),
+        }
+    }
+}
+
+impl EmbeddingConfig {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn similarity_threshold(&self) -> f32 {
+        self.similarity_threshold
+    }
+    pub fn max_similar(&self) -> usize {
+        self.max_similar
+    }
+}
+
+fn default_cache_enabled() -> bool {
+    true
+}
+
+fn default_ttl_hours() -> u64 {
+    24
+}
+
+/// Reuse of provider responses for prompts sent again within the TTL, e.g.
+/// while re-running the reader during debugging.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheConfig {
+    #[serde(default = "default_cache_enabled")]
+    enabled: bool,
+    #[serde(default = "default_ttl_hours")]
+    ttl_hours: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            enabled: default_cache_enabled(),
+            ttl_hours: default_ttl_hours(),
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn ttl_hours(&self) -> u64 {
+        self.ttl_hours
+    }
+}
+
+fn default_upcoming_days() -> u32 {
+    7
+}
+
+/// The todo agenda at the top of the digest and its calendar attachment.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TodoConfig {
+    /// Days ahead, counting today, listed under "Due soon".
+    #[serde(default = "default_upcoming_days")]
+    upcoming_days: u32,
+    /// Attach the unfinished todos to the digest as `todos.ics`.
+    #[serde(default)]
+    attach_ics: bool,
+    /// Add an all-day event on each due date to the attached calendar.
+    #[serde(default)]
+    ics_events: bool,
+}
+
+impl Default for TodoConfig {
+    fn default() -> Self {
+        TodoConfig {
+            upcoming_days: default_upcoming_days(),
+            attach_ics: false,
+            ics_events: false,
+        }
+    }
+}
+
+impl TodoConfig {
+    pub fn upcoming_days(&self) -> u32 {
+        self.upcoming_days
+    }
+    pub fn attach_ics(&self) -> bool {
+        self.attach_ics
+    }
+    pub fn ics_events(&self) -> bool {
+        self.ics_events
+    }
+}
+
+fn default_min_age_days() -> u32 {
+    30
+}
+
+/// How older thoughts are picked for the digest's resurfaced section.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResurfaceMode {
+    /// Any old enough reviewed thought, chosen at random.
+    Random,
+    /// Thoughts come back after `min_age_days`, then after twice as long
+    /// each time they are shown again.
+    #[default]
+    Spaced,
+}
+
+/// Older reviewed thoughts mixed into each digest so ideas are not buried.
+/// Off while `count` is 0.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResurfaceConfig {
+    #[serde(default)]
+    count: u32,
+    #[serde(default = "default_min_age_days")]
+    min_age_days: u32,
+    #[serde(default)]
+    mode: ResurfaceMode,
+}
+
+impl Default for ResurfaceConfig {
+    fn default() -> Self {
+        ResurfaceConfig {
+            count: 0,
+            min_age_days: default_min_age_days(),
+            mode: ResurfaceMode::default(),
+        }
+    }
+}
+
+impl ResurfaceConfig {
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+    pub fn min_age_days(&self) -> u32 {
+        self.min_age_days
+    }
+    pub fn mode(&self) -> ResurfaceMode {
+        self.mode
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     ai_client_config: AIClientConfig,
     email_config: EmailConfig,
+    #[serde(default)]
+    prompts: PromptConfig,
+    #[serde(default)]
+    retry: RetryConfig,
+    #[serde(default)]
+    digest: DigestConfig,
+    #[serde(default)]
+    embeddings: EmbeddingConfig,
+    #[serde(default)]
+    questions: QuestionConfig,
+    #[serde(default)]
+    usage: UsageConfig,
+    #[serde(default)]
+    cache: CacheConfig,
+    #[serde(default)]
+    resurface: ResurfaceConfig,
+    #[serde(default)]
+    todos: TodoConfig,
+    /// Set from the `--verbose` flag rather than the file.
+    #[serde(skip)]
+    verbose: bool,
 }
 
 impl Config {
     pub fn ai_client_config(&self) -> AIClientConfig {
-        let bearer_token = match self.ai_client_config.ai_client {
-            AIClient::Claude => self.ai_client_config.bearer_token.clone(),
-            _ => format!("Bearer {}", self.ai_client_config.bearer_token.clone()),
+        Self::with_auth_scheme(self.ai_client_config.clone())
+    }
+
+    fn with_auth_scheme(config: AIClientConfig) -> AIClientConfig {
+        // Only the Authorization header takes a scheme; Gemini's and
+        // Claude's API key headers want the bare key.
+        let bearer_token = match config.ai_client {
+            AIClient::Gemini | AIClient::Claude => config.bearer_token.clone(),
+            AIClient::Ollama if config.bearer_token.is_empty() => String::new(),
+            AIClient::OpenAI | AIClient::Ollama => format!("Bearer {}", config.bearer_token),
         };
 
         AIClientConfig {
-            ai_client: self.ai_client_config.ai_client.clone(),
             bearer_token,
+            ..config
         }
     }
 
+    /// Client settings for embedding requests. A different provider in
+    /// `[embeddings]` does not inherit the main provider's URL or token.
+    pub fn embedding_client_config(&self) -> Result<AIClientConfig, AppError> {
+        let embeddings = &self.embeddings;
+        let main = &self.ai_client_config;
+        let ai_client = embeddings
+            .ai_client
+            .clone()
+            .unwrap_or(main.ai_client.clone());
+        let same_provider = embeddings.ai_client.is_none();
+        let model = match &embeddings.model {
+            Some(model) => model.clone(),
+            None => ai_client
+                .default_embedding_model()
+                .ok_or_else(|| {
+                    AppError::Config(format!(
+                        "{:?} has no embeddings API, set [embeddings] ai_client",
+                        ai_client
+                    ))
+                })?
+                .to_string(),
+        };
+        let base_url = embeddings
+            .base_url
+            .clone()
+            .or_else(|| main.base_url.clone().filter(|_| same_provider));
+        let bearer_token = embeddings
+            .bearer_token
+            .clone()
+            .or_else(|| same_provider.then(|| main.bearer_token.clone()))
+            .unwrap_or_default();
+        Ok(Self::with_auth_scheme(AIClientConfig {
+            bearer_token,
+            ai_client,
+            model: Some(model),
+            base_url,
+            ..main.clone()
+        }))
+    }
+
+    pub fn load(path: &Path) -> Result<Config, AppError> {
+        Args::load_config(path)
+    }
+
     pub fn email_config(&self) -> &EmailConfig {
         &self.email_config
     }
+
+    pub fn prompts(&self) -> &PromptConfig {
+        &self.prompts
+    }
+
+    pub fn retry(&self) -> &RetryConfig {
+        &self.retry
+    }
+
+    pub fn digest(&self) -> &DigestConfig {
+        &self.digest
+    }
+
+    pub fn embeddings(&self) -> &EmbeddingConfig {
+        &self.embeddings
+    }
+
+    pub fn questions(&self) -> &QuestionConfig {
+        &self.questions
+    }
+
+    pub fn usage(&self) -> &UsageConfig {
+        &self.usage
+    }
+
+    pub fn cache(&self) -> &CacheConfig {
+        &self.cache
+    }
+
+    pub fn resurface(&self) -> &ResurfaceConfig {
+        &self.resurface
+    }
+
+    pub fn todos(&self) -> &TodoConfig {
+        &self.todos
+    }
+
+    pub fn verbose(&self) -> bool {
+        self.verbose
+    }
 }
 
 #[cfg(test)]
@@ -187,6 +1170,8 @@ mod tests {
         let args = Args {
             config: temp_file.path().to_path_buf(),
             verbose: true,
+            no_cache: false,
+            command: None,
         };
 
         let config = args.config().unwrap();
@@ -198,6 +1183,8 @@ mod tests {
         let args = Args {
             config: PathBuf::from("/nonexistent/path/config.toml"),
             verbose: true,
+            no_cache: false,
+            command: None,
         };
 
         let result = args.config();
@@ -261,7 +1248,7 @@ mod tests {
 
         let config = Args::parse_config(toml_content).unwrap();
         let ai_config = config.ai_client_config();
-        assert_eq!(ai_config.bearer_token(), "Bearer gemini_key");
+        assert_eq!(ai_config.bearer_token(), "gemini_key");
     }
 
     #[test]
@@ -287,4 +1274,411 @@ mod tests {
         assert_eq!(email.relay(), "smtp.gmail.com");
         assert_eq!(email.name(), "John Doe");
     }
+
+    #[test]
+    fn test_ai_client_config_defaults() {
+        let toml_content = r#"
+            [ai_client_config]
+            bearer_token = "token"
+            ai_client = "Gemini"
+
+            [email_config]
+            sender_email = "test@test.com"
+            receiver_email = "test@test.com"
+            app_password = "pass"
+            relay = "smtp.test.com"
+            name = "Test"
+        "#;
+
+        let config = Args::parse_config(toml_content).unwrap();
+        let ai_config = config.ai_client_config();
+        assert_eq!(ai_config.model(), "gemini-2.0-flash");
+        assert_eq!(ai_config.temperature(), 0.7);
+        assert_eq!(ai_config.max_output_tokens(), 4096);
+        assert_eq!(ai_config.timeout_secs(), 120);
+        assert_eq!(
+            ai_config.endpoint(),
+            "https://generativelanguage.googleapis.com/v1/models/gemini-2.0-flash:generateContent"
+        );
+    }
+
+    #[test]
+    fn test_ai_client_config_overrides() {
+        let toml_content = r#"
+            [ai_client_config]
+            bearer_token = "token"
+            ai_client = "OpenAI"
+            model = "gpt-4.1"
+            temperature = 0.2
+            max_output_tokens = 512
+            timeout_secs = 30
+            base_url = "http://localhost:8080/"
+
+            [email_config]
+            sender_email = "test@test.com"
+            receiver_email = "test@test.com"
+            app_password = "pass"
+            relay = "smtp.test.com"
+            name = "Test"
+        "#;
+
+        let config = Args::parse_config(toml_content).unwrap();
+        let ai_config = config.ai_client_config();
+        assert_eq!(ai_config.model(), "gpt-4.1");
+        assert_eq!(ai_config.temperature(), 0.2);
+        assert_eq!(ai_config.max_output_tokens(), 512);
+        assert_eq!(ai_config.timeout_secs(), 30);
+        assert_eq!(
+            ai_config.endpoint(),
+            "http://localhost:8080/v1/chat/completions"
+        );
+    }
+
+    #[test]
+    fn test_gemini_endpoint_uses_configured_model() {
+        let endpoint = AIClient::Gemini.endpoint("http://localhost:9000", "gemini-2.5-pro");
+        assert_eq!(
+            endpoint,
+            "http://localhost:9000/v1/models/gemini-2.5-pro:generateContent"
+        );
+    }
+
+    #[test]
+    fn test_ollama_config_without_token() {
+        let toml_content = r#"
+            [ai_client_config]
+            ai_client = "Ollama"
+            model = "mistral"
+
+            [email_config]
+            sender_email = "test@test.com"
+            receiver_email = "test@test.com"
+            app_password = "pass"
+            relay = "smtp.test.com"
+            name = "Test"
+        "#;
+
+        let config = Args::parse_config(toml_content).unwrap();
+        let ai_config = config.ai_client_config();
+        assert_eq!(ai_config.bearer_token(), "");
+        assert_eq!(ai_config.model(), "mistral");
+        assert_eq!(
+            ai_config.endpoint(),
+            "http://localhost:11434/v1/chat/completions"
+        );
+    }
+
+    #[test]
+    fn test_prompt_config_defaults_to_project_only() {
+        let toml_content = r#"
+            [ai_client_config]
+            bearer_token = "token"
+            ai_client = "Gemini"
+
+            [email_config]
+            sender_email = "test@test.com"
+            receiver_email = "test@test.com"
+            app_password = "pass"
+            relay = "smtp.test.com"
+            name = "Test"
+        "#;
+
+        let config = Args::parse_config(toml_content).unwrap();
+        assert_eq!(config.prompts().analyse(), [ThoughtType::Project]);
+        assert_eq!(config.prompts().mode(), AnalysisMode::Batch);
+        assert!(
+            config
+                .prompts()
+                .template_path(&ThoughtType::Project)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_prompt_config_per_type_templates() {
+        let toml_content = r#"
+            [ai_client_config]
+            bearer_token = "token"
+            ai_client = "Gemini"
+
+            [email_config]
+            sender_email = "test@test.com"
+            receiver_email = "test@test.com"
+            app_password = "pass"
+            relay = "smtp.test.com"
+            name = "Test"
+
+            [prompts]
+            analyse = ["Project", "Question", "Todo"]
+            mode = "per_thought"
+            question = "prompts/research_plan.txt"
+        "#;
+
+        let config = Args::parse_config(toml_content).unwrap();
+        let prompts = config.prompts();
+        assert_eq!(prompts.mode(), AnalysisMode::PerThought);
+        assert_eq!(
+            prompts.analyse(),
+            [
+                ThoughtType::Project,
+                ThoughtType::Question,
+                ThoughtType::Todo
+            ]
+        );
+        assert_eq!(
+            prompts.template_path(&ThoughtType::Question).unwrap(),
+            &PathBuf::from("prompts/research_plan.txt")
+        );
+        assert!(prompts.template_path(&ThoughtType::Todo).is_none());
+    }
+
+    #[test]
+    fn test_args_show_command() {
+        let args = Args::try_parse_from(["thought", "show", "42"]).unwrap();
+        assert!(matches!(args.command(), Some(Command::Show { id: 42 })));
+
+        let args = Args::try_parse_from(["thought"]).unwrap();
+        assert!(args.command().is_none());
+    }
+
+    #[test]
+    fn test_embedding_client_config() {
+        let base = r#"
+            [ai_client_config]
+            bearer_token = "token"
+            ai_client = "OpenAI"
+            base_url = "https://proxy.example.com"
+
+            [email_config]
+            sender_email = "test@test.com"
+            receiver_email = "test@test.com"
+            app_password = "pass"
+            relay = "smtp.test.com"
+            name = "Test"
+        "#;
+
+        let config = Args::parse_config(base).unwrap();
+        assert!(!config.embeddings().enabled());
+        let embedding = config.embedding_client_config().unwrap();
+        assert_eq!(embedding.model(), "text-embedding-3-small");
+        assert_eq!(embedding.bearer_token(), "Bearer token");
+        assert_eq!(
+            embedding.embedding_endpoint().unwrap(),
+            "https://proxy.example.com/v1/embeddings"
+        );
+
+        let config = Args::parse_config(&format!(
+            "{}\n[embeddings]\nenabled = true\nai_client = \"Ollama\"",
+            base
+        ))
+        .unwrap();
+        let embedding = config.embedding_client_config().unwrap();
+        assert_eq!(embedding.model(), "nomic-embed-text");
+        assert_eq!(embedding.bearer_token(), "");
+        assert_eq!(
+            embedding.embedding_endpoint().unwrap(),
+            "http://localhost:11434/v1/embeddings"
+        );
+        assert_eq!(config.embeddings().similarity_threshold(), 0.85);
+
+        let claude = base.replace("\"OpenAI\"", "\"Claude\"");
+        let config = Args::parse_config(&claude).unwrap();
+        assert!(matches!(
+            config.embedding_client_config(),
+            Err(AppError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_args_classification_commands() {
+        let args = Args::try_parse_from(["thought", "reclassify", "--limit", "5"]).unwrap();
+        assert!(matches!(
+            args.command(),
+            Some(Command::Reclassify {
+                thought_type: ThoughtType::Misc,
+                limit: 5
+            })
+        ));
+
+        let args = Args::try_parse_from(["thought", "accept", "3", "4"]).unwrap();
+        assert!(matches!(args.command(), Some(Command::Accept { ids }) if ids == &[3, 4]));
+
+        assert!(Args::try_parse_from(["thought", "reject"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_flags_override_config() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        let toml_content = r#"
+            [ai_client_config]
+            ai_client = "Ollama"
+
+            [email_config]
+            sender_email = "test@example.com"
+            receiver_email = "dest@example.com"
+            app_password = "pass"
+            relay = "smtp.test.com"
+            name = "Tester"
+
+            [cache]
+            ttl_hours = 2
+        "#;
+        temp_file.write_all(toml_content.as_bytes()).unwrap();
+        let path = temp_file.path().to_str().unwrap();
+
+        let config = Args::try_parse_from(["thought", "-c", path])
+            .unwrap()
+            .config()
+            .unwrap();
+        assert!(config.cache().enabled());
+        assert_eq!(config.cache().ttl_hours(), 2);
+        assert!(!config.verbose());
+
+        let config = Args::try_parse_from(["thought", "-c", path, "-v", "--no-cache", "usage"])
+            .unwrap()
+            .config()
+            .unwrap();
+        assert!(!config.cache().enabled());
+        assert!(config.verbose());
+    }
+
+    #[test]
+    fn test_args_unreview_needs_ids_or_filter() {
+        assert!(Args::try_parse_from(["thought", "unreview"]).is_err());
+        let args = Args::try_parse_from(["thought", "unreview", "--tag", "cli"]).unwrap();
+        let Some(Command::Unreview { ids, filter }) = args.command() else {
+            panic!("expected unreview command");
+        };
+        assert!(ids.is_empty());
+        assert_eq!(filter.tag(), Some("cli"));
+
+        for flag in ["--pinned", "--starred", "--tree"] {
+            assert!(Args::try_parse_from(["thought", "unreview", flag]).is_ok());
+        }
+        let args = Args::try_parse_from(["thought", "unreview", "--state", "archived"]).unwrap();
+        let Some(Command::Unreview { filter, .. }) = args.command() else {
+            panic!("expected unreview command");
+        };
+        assert_eq!(filter.state(), ThoughtState::Archived);
+    }
+
+    #[test]
+    fn test_args_list_command() {
+        let args = Args::try_parse_from([
+            "thought",
+            "list",
+            "--thought-type",
+            "project",
+            "--unreviewed",
+            "--since",
+            "2025-01-01",
+            "--sort",
+            "id",
+            "--page",
+            "2",
+            "--format",
+            "ndjson",
+        ])
+        .unwrap();
+        let Some(Command::List { filter, format }) = args.command() else {
+            panic!("expected list command");
+        };
+        assert_eq!(filter.thought_type(), Some(&ThoughtType::Project));
+        assert_eq!(filter.reviewed(), Some(false));
+        assert_eq!(filter.since(), Some("2025-01-01"));
+        assert_eq!(filter.sort(), SortBy::Id);
+        assert_eq!((filter.limit(), filter.page()), (20, 2));
+        assert_eq!(*format, OutputFormat::Ndjson);
+
+        for bad in [
+            ["thought", "list", "--since", "2025-1-1"],
+            ["thought", "list", "--page", "0"],
+            ["thought", "list", "--reviewed", "--unreviewed"],
+        ] {
+            assert!(Args::try_parse_from(bad).is_err());
+        }
+    }
+
+    #[test]
+    fn test_args_edit_command() {
+        let args = Args::try_parse_from(["thought", "edit", "4", "--type", "todo"]).unwrap();
+        assert!(matches!(
+            args.command(),
+            Some(Command::Edit {
+                id: 4,
+                content: None,
+                thought_type: Some(ThoughtType::Todo)
+            })
+        ));
+
+        assert!(Args::try_parse_from(["thought", "delete"]).is_err());
+    }
+
+    #[test]
+    fn test_args_usage_command() {
+        let args = Args::try_parse_from(["thought", "usage", "--month", "2025-01"]).unwrap();
+        assert!(matches!(
+            args.command(),
+            Some(Command::Usage { month: Some(m), all: false }) if m == "2025-01"
+        ));
+
+        assert!(Args::try_parse_from(["thought", "usage", "--all", "--month", "2025-01"]).is_err());
+    }
+
+    #[test]
+    fn test_retry_config_defaults_and_overrides() {
+        let base = r#"
+            [ai_client_config]
+            bearer_token = "token"
+            ai_client = "Gemini"
+
+            [email_config]
+            sender_email = "test@test.com"
+            receiver_email = "test@test.com"
+            app_password = "pass"
+            relay = "smtp.test.com"
+            name = "Test"
+        "#;
+
+        let config = Args::parse_config(base).unwrap();
+        assert_eq!(config.retry().max_attempts(), 3);
+        assert_eq!(config.retry().initial_backoff_ms(), 500);
+        assert!(config.retry().jitter());
+
+        let config = Args::parse_config(&format!(
+            "{}\n[retry]\nmax_attempts = 5\njitter = false",
+            base
+        ))
+        .unwrap();
+        assert_eq!(config.retry().max_attempts(), 5);
+        assert_eq!(config.retry().max_backoff_ms(), 30_000);
+        assert!(!config.retry().jitter());
+    }
+
+    #[test]
+    fn test_structured_mode_and_digest_config() {
+        let toml_content = r#"
+            [ai_client_config]
+            bearer_token = "token"
+            ai_client = "Gemini"
+
+            [email_config]
+            sender_email = "test@test.com"
+            receiver_email = "test@test.com"
+            app_password = "pass"
+            relay = "smtp.test.com"
+            name = "Test"
+
+            [prompts]
+            mode = "structured"
+
+            [digest]
+            min_novelty_score = 6
+        "#;
+
+        let config = Args::parse_config(toml_content).unwrap();
+        assert_eq!(config.prompts().mode(), AnalysisMode::Structured);
+        assert!(config.digest().sort_by_score());
+        assert_eq!(config.digest().min_novelty_score(), Some(6));
+    }
 }
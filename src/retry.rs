@@ -0,0 +1,179 @@
+use crate::reader_config::RetryConfig;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::thread;
+use std::time::Duration;
+
+/// A failed attempt, marked as worth retrying or not.
+#[derive(Debug)]
+pub struct RetryError<E> {
+    error: E,
+    transient: bool,
+    retry_after: Option<Duration>,
+}
+
+impl<E> RetryError<E> {
+    /// A failure that may succeed if tried again, e.g. a 503 or a dropped
+    /// connection.
+    pub fn transient(error: E) -> RetryError<E> {
+        RetryError {
+            error,
+            transient: true,
+            retry_after: None,
+        }
+    }
+
+    /// A failure that will not go away by retrying, e.g. a bad API key.
+    pub fn permanent(error: E) -> RetryError<E> {
+        RetryError {
+            error,
+            transient: false,
+            retry_after: None,
+        }
+    }
+
+    /// The delay the server asked for before the next attempt.
+    pub fn with_retry_after(mut self, retry_after: Option<Duration>) -> RetryError<E> {
+        self.retry_after = retry_after;
+        self
+    }
+
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+/// Parses a `Retry-After` header given in seconds. HTTP dates are ignored and
+/// fall back to the regular backoff.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    (hasher.finish() % 10_000) as f64 / 10_000.0
+}
+
+/// Exponential backoff for the given attempt (1 based), capped at the
+/// configured maximum. With jitter the delay is picked at random from the
+/// upper half of the window so concurrent runs spread out.
+pub fn backoff(config: &RetryConfig, attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(31);
+    let delay = config
+        .initial_backoff_ms()
+        .saturating_mul(1u64 << exponent)
+        .min(config.max_backoff_ms());
+    let delay = if config.jitter() {
+        delay / 2 + ((delay / 2) as f64 * random_fraction()) as u64
+    } else {
+        delay
+    };
+    Duration::from_millis(delay)
+}
+
+/// Runs `operation` until it succeeds, fails permanently, or runs out of
+/// attempts. A server supplied `Retry-After` replaces the backoff, capped at
+/// the configured maximum.
+pub fn retry<T, E, F>(config: &RetryConfig, mut operation: F) -> Result<T, E>
+where
+    F: FnMut() -> Result<T, RetryError<E>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Ok(value) => return Ok(value),
+            Err(err) if !err.transient || attempt >= config.max_attempts() => {
+                return Err(err.into_inner());
+            }
+            Err(err) => {
+                let max_backoff = Duration::from_millis(config.max_backoff_ms());
+                let delay = err
+                    .retry_after
+                    .map(|d| d.min(max_backoff))
+                    .unwrap_or_else(|| backoff(config, attempt));
+                thread::sleep(delay);
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn test_config(max_attempts: u32, jitter: bool) -> RetryConfig {
+        toml::from_str(&format!(
+            "max_attempts = {}\ninitial_backoff_ms = 1\nmax_backoff_ms = 8\njitter = {}",
+            max_attempts, jitter
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_retry_succeeds_after_transient_failures() {
+        let calls = Cell::new(0);
+        let result: Result<&str, String> = retry(&test_config(3, true), || {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 {
+                Err(RetryError::transient("busy".to_string()))
+            } else {
+                Ok("done")
+            }
+        });
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn test_retry_gives_up_after_max_attempts() {
+        let calls = Cell::new(0);
+        let result: Result<(), String> = retry(&test_config(4, false), || {
+            calls.set(calls.get() + 1);
+            Err(RetryError::transient(format!("attempt {}", calls.get())))
+        });
+        assert_eq!(result.unwrap_err(), "attempt 4");
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn test_retry_stops_on_permanent_failure() {
+        let calls = Cell::new(0);
+        let result: Result<(), String> = retry(&test_config(5, false), || {
+            calls.set(calls.get() + 1);
+            Err(RetryError::permanent("bad key".to_string()))
+        });
+        assert_eq!(result.unwrap_err(), "bad key");
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let config = test_config(10, false);
+        assert_eq!(backoff(&config, 1), Duration::from_millis(1));
+        assert_eq!(backoff(&config, 2), Duration::from_millis(2));
+        assert_eq!(backoff(&config, 3), Duration::from_millis(4));
+        assert_eq!(backoff(&config, 4), Duration::from_millis(8));
+        assert_eq!(backoff(&config, 40), Duration::from_millis(8));
+    }
+
+    #[test]
+    fn test_backoff_jitter_stays_in_window() {
+        let config: RetryConfig =
+            toml::from_str("initial_backoff_ms = 1000\nmax_backoff_ms = 1000").unwrap();
+        for _ in 0..20 {
+            let delay = backoff(&config, 1);
+            assert!(delay >= Duration::from_millis(500));
+            assert!(delay <= Duration::from_millis(1000));
+        }
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("3"), Some(Duration::from_secs(3)));
+        assert_eq!(parse_retry_after(" 0 "), Some(Duration::from_secs(0)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+}
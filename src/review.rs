@@ -0,0 +1,116 @@
+use serde::Serialize;
+
+const INITIAL_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+
+/// Highest review rating: 5 is perfect recall, below 3 a lapse.
+pub const MAX_QUALITY: u8 = 5;
+
+/// SM-2 review schedule of a thought the user has rated at least once.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReviewSchedule {
+    repetitions: u32,
+    interval_days: u32,
+    ease: f64,
+    next_review_at: String,
+}
+
+impl Default for ReviewSchedule {
+    fn default() -> Self {
+        ReviewSchedule {
+            repetitions: 0,
+            interval_days: 0,
+            ease: INITIAL_EASE,
+            next_review_at: String::new(),
+        }
+    }
+}
+
+impl ReviewSchedule {
+    pub fn new(
+        repetitions: u32,
+        interval_days: u32,
+        ease: f64,
+        next_review_at: String,
+    ) -> ReviewSchedule {
+        ReviewSchedule {
+            repetitions,
+            interval_days,
+            ease,
+            next_review_at,
+        }
+    }
+
+    /// The schedule after a review rated `quality` from 0 to 5. A rating of
+    /// 3 or more grows the interval from 1 day to 6 and then by the ease
+    /// factor; a lower rating starts over at 1 day. The ease moves with
+    /// every rating but never drops below 1.3. The next review date is left
+    /// for the database to fill in.
+    pub fn next(&self, quality: u8) -> ReviewSchedule {
+        let quality = quality.min(MAX_QUALITY);
+        let (repetitions, interval_days) = if quality >= 3 {
+            let interval = match self.repetitions {
+                0 => 1,
+                1 => 6,
+                _ => (self.interval_days as f64 * self.ease).round() as u32,
+            };
+            (self.repetitions + 1, interval)
+        } else {
+            (0, 1)
+        };
+        let lapse = (MAX_QUALITY - quality) as f64;
+        let ease = (self.ease + 0.1 - lapse * (0.08 + lapse * 0.02)).max(MIN_EASE);
+        ReviewSchedule {
+            repetitions,
+            interval_days,
+            ease,
+            next_review_at: String::new(),
+        }
+    }
+
+    pub fn repetitions(&self) -> u32 {
+        self.repetitions
+    }
+    pub fn interval_days(&self) -> u32 {
+        self.interval_days
+    }
+    pub fn ease(&self) -> f64 {
+        self.ease
+    }
+    /// UTC, YYYY-MM-DD HH:MM:SS.
+    pub fn next_review_at(&self) -> &str {
+        &self.next_review_at
+    }
+    pub fn next_review_on(&self) -> &str {
+        self.next_review_at
+            .get(..10)
+            .unwrap_or(&self.next_review_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_grows_interval_with_good_ratings() {
+        let first = ReviewSchedule::default().next(5);
+        assert_eq!((first.repetitions(), first.interval_days()), (1, 1));
+        assert!((first.ease() - 2.6).abs() < 1e-9);
+
+        let second = first.next(4);
+        assert_eq!((second.repetitions(), second.interval_days()), (2, 6));
+        assert!((second.ease() - 2.6).abs() < 1e-9);
+
+        let third = second.next(3);
+        assert_eq!((third.repetitions(), third.interval_days()), (3, 16));
+        assert!((third.ease() - 2.46).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_next_restarts_after_lapse_and_keeps_minimum_ease() {
+        let lapsed = ReviewSchedule::new(4, 30, 1.4, String::new()).next(1);
+        assert_eq!((lapsed.repetitions(), lapsed.interval_days()), (0, 1));
+        assert_eq!(lapsed.ease(), MIN_EASE);
+    }
+}
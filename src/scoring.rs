@@ -0,0 +1,151 @@
+use crate::errors::AppError;
+use crate::provider::json_payload;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// Appended to the rendered prompt in structured mode. Providers without
+/// native schema support rely on it alone.
+pub const STRUCTURED_INSTRUCTIONS: &str = r#"
+
+Respond with a single JSON object only, no markdown, with these fields:
+- "summary": one sentence describing the idea
+- "scale": one of "hobby", "side project", "startup", "enterprise", "research"
+- "effort_estimate": rough effort, e.g. "2 weekends" or "3 months"
+- "novelty_score": integer from 1 (done many times) to 10 (genuinely new)
+- "risks": list of the main risks or challenges
+- "next_steps": list of concrete first actions"#;
+
+/// Machine readable assessment of a single idea.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IdeaScore {
+    summary: String,
+    scale: String,
+    effort_estimate: String,
+    novelty_score: u8,
+    risks: Vec<String>,
+    next_steps: Vec<String>,
+}
+
+impl IdeaScore {
+    pub fn new(
+        summary: String,
+        scale: String,
+        effort_estimate: String,
+        novelty_score: u8,
+        risks: Vec<String>,
+        next_steps: Vec<String>,
+    ) -> IdeaScore {
+        IdeaScore {
+            summary,
+            scale,
+            effort_estimate,
+            novelty_score,
+            risks,
+            next_steps,
+        }
+    }
+
+    /// Parses and validates a model response. Tolerates a markdown code fence
+    /// around the JSON, which some models add despite instructions.
+    pub fn from_response(text: &str) -> Result<IdeaScore, AppError> {
+        let score: IdeaScore = serde_json::from_str(json_payload(text))?;
+        if !(1..=10).contains(&score.novelty_score) {
+            return Err(AppError::Json(format!(
+                "novelty_score {} is outside 1-10",
+                score.novelty_score
+            )));
+        }
+        Ok(score)
+    }
+
+    pub fn summary(&self) -> &str {
+        &self.summary
+    }
+    pub fn scale(&self) -> &str {
+        &self.scale
+    }
+    pub fn effort_estimate(&self) -> &str {
+        &self.effort_estimate
+    }
+    pub fn novelty_score(&self) -> u8 {
+        self.novelty_score
+    }
+    pub fn risks(&self) -> &[String] {
+        &self.risks
+    }
+    pub fn next_steps(&self) -> &[String] {
+        &self.next_steps
+    }
+}
+
+/// JSON schema for [`IdeaScore`], in the subset understood by both Gemini's
+/// `responseSchema` and OpenAI's strict `json_schema` response format.
+pub fn idea_schema() -> Value {
+    let string_list = json!({"type": "array", "items": {"type": "string"}});
+    json!({
+        "type": "object",
+        "properties": {
+            "summary": {"type": "string"},
+            "scale": {
+                "type": "string",
+                "enum": ["hobby", "side project", "startup", "enterprise", "research"]
+            },
+            "effort_estimate": {"type": "string"},
+            "novelty_score": {"type": "integer"},
+            "risks": string_list,
+            "next_steps": string_list
+        },
+        "required": ["summary", "scale", "effort_estimate", "novelty_score", "risks", "next_steps"]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: &str = r#"{
+        "summary": "CLI habit tracker",
+        "scale": "hobby",
+        "effort_estimate": "2 weekends",
+        "novelty_score": 3,
+        "risks": ["Crowded space"],
+        "next_steps": ["Sketch the data model", "Build a prototype"]
+    }"#;
+
+    #[test]
+    fn test_from_response_valid() {
+        let score = IdeaScore::from_response(VALID).unwrap();
+        assert_eq!(score.scale(), "hobby");
+        assert_eq!(score.novelty_score(), 3);
+        assert_eq!(score.next_steps().len(), 2);
+    }
+
+    #[test]
+    fn test_from_response_strips_code_fence() {
+        let fenced = format!("```json\n{}\n```", VALID);
+        assert!(IdeaScore::from_response(&fenced).is_ok());
+    }
+
+    #[test]
+    fn test_from_response_rejects_out_of_range_score() {
+        let invalid = VALID.replace("\"novelty_score\": 3", "\"novelty_score\": 11");
+        assert!(matches!(
+            IdeaScore::from_response(&invalid),
+            Err(AppError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_response_rejects_missing_fields() {
+        let result = IdeaScore::from_response(r#"{"summary": "Only a summary"}"#);
+        assert!(matches!(result, Err(AppError::Json(_))));
+    }
+
+    #[test]
+    fn test_schema_requires_every_field() {
+        let schema = idea_schema();
+        let required = schema["required"].as_array().unwrap();
+        let properties = schema["properties"].as_object().unwrap();
+        assert_eq!(required.len(), properties.len());
+    }
+}
@@ -0,0 +1,64 @@
+use crate::client::Session;
+use crate::errors::AppError;
+use crate::provider::json_payload;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+const SUBTASKS_PROMPT: &str = r#"Break the thought below into 3 to 7 concrete subtasks that can each be done in one sitting. Start each subtask with a verb, keep it under 80 characters and order the subtasks so each one can start when the previous one is done.
+
+Respond with a single JSON object only, no markdown, with the field "subtasks" (list of strings).
+
+Thought:
+"#;
+
+#[derive(Deserialize)]
+struct Subtasks {
+    subtasks: Vec<String>,
+}
+
+fn subtasks_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "subtasks": {"type": "array", "items": {"type": "string"}}
+        },
+        "required": ["subtasks"]
+    })
+}
+
+/// Parses a model response into trimmed, non-empty subtasks.
+pub fn parse_subtasks(text: &str) -> Result<Vec<String>, AppError> {
+    let parsed: Subtasks = serde_json::from_str(json_payload(text))?;
+    Ok(parsed
+        .subtasks
+        .iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect())
+}
+
+/// Asks the configured provider to break the content into subtasks.
+pub fn suggest(session: &Session, content: &str) -> Result<Vec<String>, AppError> {
+    let prompt = format!("{}{}", SUBTASKS_PROMPT, content);
+    let response = session.structured_response(&prompt, &subtasks_schema())?;
+    parse_subtasks(&response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subtasks() {
+        let subtasks = parse_subtasks(
+            "```json\n{\"subtasks\": [\" Sketch the schema \", \"\", \"Build the CLI\"]}\n```",
+        )
+        .unwrap();
+        assert_eq!(subtasks, ["Sketch the schema", "Build the CLI"]);
+
+        assert!(matches!(
+            parse_subtasks(r#"{"steps": []}"#),
+            Err(AppError::Json(_))
+        ));
+    }
+}
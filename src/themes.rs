@@ -0,0 +1,190 @@
+use crate::client::Session;
+use crate::db_operations::load_embeddings;
+use crate::embeddings::{cosine_similarity, embed_missing};
+use crate::errors::AppError;
+use crate::provider::json_payload;
+use crate::reader_config::Config;
+use crate::thought::{Theme, Thought};
+use rusqlite::Connection;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+const NAME_PROMPT: &str = r#"Below are groups of related thoughts. Give each group a short theme name of 2 to 5 words.
+
+Respond with a single JSON object only, no markdown, with the field "names": a list holding one name per group, in the order given.
+"#;
+
+const UNGROUPED: &str = "Other thoughts";
+
+#[derive(Deserialize)]
+struct ThemeNames {
+    names: Vec<String>,
+}
+
+fn names_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "names": {"type": "array", "items": {"type": "string"}}
+        },
+        "required": ["names"]
+    })
+}
+
+fn centroid(vectors: &[&[f32]]) -> Vec<f32> {
+    let mut sum = vec![0.0; vectors.first().map_or(0, |v| v.len())];
+    for vector in vectors {
+        for (total, value) in sum.iter_mut().zip(vector.iter()) {
+            *total += value;
+        }
+    }
+    sum.iter().map(|v| v / vectors.len() as f32).collect()
+}
+
+/// Groups vectors in a single pass: each joins the cluster whose centroid it
+/// is most similar to, if at least `threshold`, or starts a new one. Clusters
+/// are returned in order of their first member.
+pub fn cluster(vectors: &[(i32, Vec<f32>)], threshold: f32) -> Vec<Vec<i32>> {
+    let mut clusters: Vec<(Vec<i32>, Vec<&[f32]>)> = Vec::new();
+    for (id, vector) in vectors {
+        let best = clusters
+            .iter()
+            .enumerate()
+            .map(|(i, (_, members))| (i, cosine_similarity(vector, &centroid(members))))
+            .filter(|(_, similarity)| *similarity >= threshold)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match best {
+            Some((i, _)) => {
+                clusters[i].0.push(*id);
+                clusters[i].1.push(vector);
+            }
+            None => clusters.push((vec![*id], vec![vector])),
+        }
+    }
+    clusters.into_iter().map(|(ids, _)| ids).collect()
+}
+
+/// The tag shared by most members, used when the provider cannot name a
+/// cluster.
+fn fallback_name(members: &[&Thought], number: usize) -> String {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for tag in members.iter().flat_map(|t| t.tags()) {
+        match counts.iter_mut().find(|(name, _)| name == tag) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((tag, 1)),
+        }
+    }
+    counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .max_by_key(|(_, count)| *count)
+        .map(|(tag, _)| tag.to_string())
+        .unwrap_or_else(|| format!("Theme {}", number))
+}
+
+/// Asks the provider for one name per group of thoughts.
+pub fn name_clusters(session: &Session, groups: &[Vec<&Thought>]) -> Result<Vec<String>, AppError> {
+    let listing = groups
+        .iter()
+        .enumerate()
+        .map(|(i, members)| {
+            let items = members
+                .iter()
+                .map(|t| format!("- {}", t.content()))
+                .collect::<Vec<String>>()
+                .join("\n");
+            format!("Group {}:\n{}", i + 1, items)
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n");
+    let prompt = format!("{}\n{}", NAME_PROMPT, listing);
+    let response = session.structured_response(&prompt, &names_schema())?;
+    let names: ThemeNames = serde_json::from_str(json_payload(&response))?;
+    if names.names.len() != groups.len() {
+        return Err(AppError::Json(format!(
+            "expected {} theme names, got {}",
+            groups.len(),
+            names.names.len()
+        )));
+    }
+    Ok(names.names)
+}
+
+/// Clusters the thoughts by embedding and names each cluster of two or more.
+/// Thoughts that fit no cluster are collected under a final "Other thoughts"
+/// theme. Returns no themes when nothing clusters, leaving the flat list.
+pub fn group(
+    conn: &Connection,
+    config: &Config,
+    thoughts: &[Thought],
+) -> Result<Vec<Theme>, AppError> {
+    let model = embed_missing(conn, config)?;
+    let vectors: Vec<(i32, Vec<f32>)> = load_embeddings(conn, &model)?
+        .into_iter()
+        .filter(|(id, _)| thoughts.iter().any(|t| t.id() == id))
+        .collect();
+    let (clusters, singles): (Vec<Vec<i32>>, Vec<Vec<i32>>) =
+        cluster(&vectors, config.digest().theme_threshold())
+            .into_iter()
+            .partition(|ids| ids.len() > 1);
+    if clusters.is_empty() {
+        return Ok(Vec::new());
+    }
+    let groups: Vec<Vec<&Thought>> = clusters
+        .iter()
+        .map(|ids| thoughts.iter().filter(|t| ids.contains(t.id())).collect())
+        .collect();
+    let session = Session::from_config(conn, config);
+    let names = name_clusters(&session, &groups).unwrap_or_else(|e| {
+        eprintln!("Could not name themes: {}", e);
+        groups
+            .iter()
+            .enumerate()
+            .map(|(i, members)| fallback_name(members, i + 1))
+            .collect()
+    });
+    let mut themes: Vec<Theme> = names
+        .into_iter()
+        .zip(clusters)
+        .map(|(name, ids)| Theme::new(name, ids))
+        .collect();
+    let others: Vec<i32> = singles.into_iter().flatten().collect();
+    if !others.is_empty() {
+        themes.push(Theme::new(UNGROUPED.to_string(), others));
+    }
+    Ok(themes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writer_config::ThoughtType;
+
+    #[test]
+    fn test_cluster_groups_similar_vectors() {
+        let vectors = [
+            (1, vec![1.0, 0.0]),
+            (2, vec![0.0, 1.0]),
+            (3, vec![0.95, 0.1]),
+            (4, vec![0.1, 0.9]),
+            (5, vec![-1.0, 0.0]),
+        ];
+
+        assert_eq!(cluster(&vectors, 0.8), [vec![1, 3], vec![2, 4], vec![5]]);
+        assert_eq!(cluster(&vectors, 0.999).len(), 5);
+    }
+
+    #[test]
+    fn test_fallback_name_uses_shared_tag() {
+        let tagged = |id: i32, tags: &[&str]| {
+            Thought::new(id, ThoughtType::Notes, String::new(), false)
+                .with_tags(tags.iter().map(|t| t.to_string()).collect())
+        };
+        let a = tagged(1, &["rust", "cli"]);
+        let b = tagged(2, &["rust"]);
+        let c = tagged(3, &[]);
+
+        assert_eq!(fallback_name(&[&a, &b], 1), "rust");
+        assert_eq!(fallback_name(&[&a, &c], 2), "Theme 2");
+    }
+}
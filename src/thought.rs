@@ -1,12 +1,64 @@
+use crate::classify::Classification;
+use crate::review::ReviewSchedule;
+use crate::scoring::IdeaScore;
+use crate::todo::{Progress, TodoDetails};
 use crate::writer_config::ThoughtType;
+use clap::ValueEnum;
 use lettre::message::header::ContentTransferEncoding;
 use lettre::message::{Body, IntoBody};
-#[derive(Debug)]
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use serde::Serialize;
+use std::str::FromStr;
+use strum_macros::{Display, EnumString};
+
+/// Where a thought is in its lifecycle. Only active thoughts reach the
+/// digest; trashed ones are permanently deleted by `empty-trash`.
+#[derive(Display, EnumString, Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ThoughtState {
+    #[default]
+    Active,
+    Archived,
+    Trashed,
+}
+
+impl FromSql for ThoughtState {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        <ThoughtState as FromStr>::from_str(value.as_str()?)
+            .map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct Thought {
     id: i32,
+    #[serde(rename = "type")]
     thought_type: ThoughtType,
     content: String,
     reviewed: bool,
+    tags: Vec<String>,
+    created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    analysis: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<IdeaScore>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suggestion: Option<Classification>,
+    answered: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    answer: Option<String>,
+    state: ThoughtState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schedule: Option<ReviewSchedule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    todo: Option<TodoDetails>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    progress: Option<Progress>,
+    pinned: bool,
+    starred: bool,
 }
 
 impl Thought {
@@ -16,9 +68,104 @@ impl Thought {
             thought_type,
             content,
             reviewed,
+            tags: Vec::new(),
+            created_at: String::new(),
+            analysis: None,
+            score: None,
+            suggestion: None,
+            answered: false,
+            answer: None,
+            state: ThoughtState::Active,
+            schedule: None,
+            todo: None,
+            parent_id: None,
+            progress: None,
+            pinned: false,
+            starred: false,
         }
     }
 
+    pub fn with_tags(mut self, tags: Vec<String>) -> Thought {
+        self.tags = tags;
+        self
+    }
+
+    pub fn with_created_at(mut self, created_at: String) -> Thought {
+        self.created_at = created_at;
+        self
+    }
+
+    pub fn with_analysis(mut self, analysis: Option<String>) -> Thought {
+        self.analysis = analysis;
+        self
+    }
+
+    pub fn set_analysis(&mut self, analysis: String) {
+        self.analysis = Some(analysis);
+    }
+
+    pub fn with_score(mut self, score: Option<IdeaScore>) -> Thought {
+        self.score = score;
+        self
+    }
+
+    pub fn set_score(&mut self, score: IdeaScore) {
+        self.score = Some(score);
+    }
+
+    pub fn with_suggestion(mut self, suggestion: Option<Classification>) -> Thought {
+        self.suggestion = suggestion;
+        self
+    }
+
+    pub fn with_answered(mut self, answered: bool) -> Thought {
+        self.answered = answered;
+        self
+    }
+
+    pub fn with_answer(mut self, answer: Option<String>) -> Thought {
+        self.answer = answer;
+        self
+    }
+
+    pub fn set_answer(&mut self, answer: String) {
+        self.answer = Some(answer);
+    }
+
+    pub fn with_state(mut self, state: ThoughtState) -> Thought {
+        self.state = state;
+        self
+    }
+
+    pub fn with_schedule(mut self, schedule: Option<ReviewSchedule>) -> Thought {
+        self.schedule = schedule;
+        self
+    }
+
+    pub fn with_todo(mut self, todo: Option<TodoDetails>) -> Thought {
+        self.todo = todo;
+        self
+    }
+
+    pub fn with_parent(mut self, parent_id: Option<i32>) -> Thought {
+        self.parent_id = parent_id;
+        self
+    }
+
+    pub fn set_progress(&mut self, progress: Progress) {
+        self.progress = Some(progress);
+    }
+
+    pub fn with_pinned(mut self, pinned: bool) -> Thought {
+        self.pinned = pinned;
+        self
+    }
+
+    pub fn with_starred(mut self, starred: bool) -> Thought {
+        self.starred = starred;
+        self
+    }
+
     pub fn id(&self) -> &i32 {
         &self.id
     }
@@ -32,56 +179,387 @@ impl Thought {
     pub fn reviewed(&self) -> bool {
         self.reviewed
     }
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+    /// Capture timestamp as stored by SQLite (`YYYY-MM-DD HH:MM:SS`, UTC).
+    pub fn created_at(&self) -> &str {
+        &self.created_at
+    }
+    /// The date part of the capture timestamp.
+    pub fn created_on(&self) -> &str {
+        self.created_at.get(..10).unwrap_or(&self.created_at)
+    }
+    pub fn analysis(&self) -> Option<&str> {
+        self.analysis.as_deref()
+    }
+    pub fn score(&self) -> Option<&IdeaScore> {
+        self.score.as_ref()
+    }
+    /// Whether a question has been marked answered.
+    pub fn answered(&self) -> bool {
+        self.answered
+    }
+    /// The AI answer to a question.
+    pub fn answer(&self) -> Option<&str> {
+        self.answer.as_deref()
+    }
+    /// A type and tag suggestion that has not been accepted or rejected yet.
+    pub fn suggestion(&self) -> Option<&Classification> {
+        self.suggestion.as_ref()
+    }
+    pub fn state(&self) -> ThoughtState {
+        self.state
+    }
+    /// The review schedule, once the thought has been rated.
+    pub fn schedule(&self) -> Option<&ReviewSchedule> {
+        self.schedule.as_ref()
+    }
+    /// Status, due date and priority, for todos only.
+    pub fn todo(&self) -> Option<&TodoDetails> {
+        self.todo.as_ref()
+    }
+    /// The thought this one was filed under, e.g. a project owning a todo.
+    pub fn parent_id(&self) -> Option<i32> {
+        self.parent_id
+    }
+    /// How many todos under the thought are done, once looked up.
+    pub fn progress(&self) -> Option<&Progress> {
+        self.progress.as_ref()
+    }
+    /// Whether the thought is shown at the top of every digest.
+    pub fn pinned(&self) -> bool {
+        self.pinned
+    }
+    /// Whether the thought is marked as important in listings and the digest.
+    pub fn starred(&self) -> bool {
+        self.starred
+    }
+}
+
+/// An extra titled list in the digest email, e.g. thoughts similar to
+/// earlier ones. Items are plain text and escaped when rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailSection {
+    title: String,
+    items: Vec<String>,
+}
+
+impl EmailSection {
+    pub fn new(title: &str, items: Vec<String>) -> EmailSection {
+        EmailSection {
+            title: title.to_string(),
+            items,
+        }
+    }
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+}
+
+/// A file sent alongside the digest, e.g. the todos as an iCalendar file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailAttachment {
+    filename: String,
+    content_type: String,
+    content: String,
+}
+
+impl EmailAttachment {
+    pub fn new(filename: &str, content_type: &str, content: String) -> EmailAttachment {
+        EmailAttachment {
+            filename: filename.to_string(),
+            content_type: content_type.to_string(),
+            content,
+        }
+    }
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
+
+/// A named group of related thoughts, rendered as its own heading in the
+/// digest email.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    name: String,
+    thought_ids: Vec<i32>,
+}
+
+impl Theme {
+    pub fn new(name: String, thought_ids: Vec<i32>) -> Theme {
+        Theme { name, thought_ids }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn thought_ids(&self) -> &[i32] {
+        &self.thought_ids
+    }
 }
 
 pub struct ThoughtsEmailBody<'a> {
     thoughts: &'a [Thought],
+    pinned: &'a [Thought],
+    leading: Vec<EmailSection>,
+    sections: Vec<EmailSection>,
+    themes: Vec<Theme>,
+    attachments: Vec<EmailAttachment>,
 }
 
 impl<'a> ThoughtsEmailBody<'a> {
     pub fn new(thoughts: &'a [Thought]) -> ThoughtsEmailBody<'a> {
-        ThoughtsEmailBody { thoughts }
+        ThoughtsEmailBody {
+            thoughts,
+            pinned: &[],
+            leading: Vec::new(),
+            sections: Vec::new(),
+            themes: Vec::new(),
+            attachments: Vec::new(),
+        }
     }
-}
 
-impl IntoBody for ThoughtsEmailBody<'_> {
-    fn into_body(self, _encoding: Option<ContentTransferEncoding>) -> Body {
-        let body_text = if self.thoughts.is_empty() {
-            r#"<html><body style="font-size: 16px;">
-    <h2>Weekly Thoughts Summary</h2>
-    <p>No thoughts recorded this week.</p>
-    </body></html>"#
-                .to_string()
-        } else {
-            let thoughts_section = self
+    /// Groups the thoughts under theme headings instead of one flat list.
+    /// Thoughts in no theme are listed after the themes.
+    pub fn with_themes(mut self, themes: Vec<Theme>) -> ThoughtsEmailBody<'a> {
+        self.themes = themes;
+        self
+    }
+
+    /// How many of the thought's ancestors are listed in the same group, for
+    /// indenting sub-thoughts under their parents.
+    fn depth(thought: &Thought, group: &[&Thought]) -> usize {
+        let mut depth = 0;
+        let mut parent_id = thought.parent_id;
+        while let Some(id) = parent_id
+            && let Some(parent) = group.iter().find(|t| t.id == id)
+        {
+            depth += 1;
+            parent_id = parent.parent_id;
+        }
+        depth
+    }
+
+    fn thoughts_html(&self) -> String {
+        let mut number = 0;
+        let mut html = String::new();
+        let mut render = |thoughts: Vec<&Thought>, html: &mut String| {
+            for thought in &thoughts {
+                number += 1;
+                html.push_str(&thought_html(
+                    number,
+                    thought,
+                    Self::depth(thought, &thoughts),
+                ));
+            }
+        };
+        if self.themes.is_empty() {
+            render(self.thoughts.iter().collect(), &mut html);
+            return html;
+        }
+        for theme in &self.themes {
+            html.push_str(&format!("<h3>{}</h3>", escape_html(&theme.name)));
+            let members = self
                 .thoughts
                 .iter()
-                .enumerate()
-                .map(|(i, thought)| {
-                    format!(
-                        r#"<div style="font-size: 14px; margin-bottom: 20px;">
-                <strong>{}. {}: </strong>
+                .filter(|t| theme.thought_ids.contains(t.id()))
+                .collect();
+            render(members, &mut html);
+        }
+        let ungrouped = self
+            .thoughts
+            .iter()
+            .filter(|t| !self.themes.iter().any(|th| th.thought_ids.contains(t.id())))
+            .collect();
+        render(ungrouped, &mut html);
+        html
+    }
+
+    /// Shows these thoughts in a fixed section at the very top, whether or
+    /// not they were reviewed before.
+    pub fn with_pinned(mut self, pinned: &'a [Thought]) -> ThoughtsEmailBody<'a> {
+        self.pinned = pinned;
+        self
+    }
+
+    /// Adds sections rendered before the thoughts, e.g. overdue todos. Empty
+    /// sections are skipped.
+    pub fn with_leading_sections(mut self, sections: Vec<EmailSection>) -> ThoughtsEmailBody<'a> {
+        self.leading
+            .extend(sections.into_iter().filter(|s| !s.items.is_empty()));
+        self
+    }
+
+    /// Sends a file with the digest, making the email multipart.
+    pub fn with_attachment(mut self, attachment: EmailAttachment) -> ThoughtsEmailBody<'a> {
+        self.attachments.push(attachment);
+        self
+    }
+
+    pub fn attachments(&self) -> &[EmailAttachment] {
+        &self.attachments
+    }
+
+    /// Adds sections rendered after the thoughts. Empty sections are skipped.
+    pub fn with_sections(mut self, sections: Vec<EmailSection>) -> ThoughtsEmailBody<'a> {
+        self.sections
+            .extend(sections.into_iter().filter(|s| !s.items.is_empty()));
+        self
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html_list(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|item| format!("<li>{}</li>", escape_html(item)))
+        .collect()
+}
+
+fn score_html(score: &IdeaScore) -> String {
+    format!(
+        r#"<div style="color: #444;">
+                <p><em>{}</em></p>
+                <p>Novelty: <strong>{}/10</strong> &middot; Scale: {} &middot; Effort: {}</p>
+                <p>Risks:</p><ul>{}</ul>
+                <p>Next steps:</p><ol>{}</ol>
+                </div>"#,
+        escape_html(score.summary()),
+        score.novelty_score(),
+        escape_html(score.scale()),
+        escape_html(score.effort_estimate()),
+        html_list(score.risks()),
+        html_list(score.next_steps())
+    )
+}
+
+fn pinned_html(pinned: &[Thought]) -> String {
+    if pinned.is_empty() {
+        return String::new();
+    }
+    let items: String = pinned
+        .iter()
+        .map(|thought| {
+            format!(
+                r#"<li style="white-space: pre-wrap;"><strong>#{} {}:</strong> {}</li>"#,
+                thought.id,
+                thought.thought_type,
+                escape_html(&thought.content)
+            )
+        })
+        .collect();
+    format!(
+        r#"<h3>Pinned</h3>
+        <ul style="font-size: 14px;">{}</ul>"#,
+        items
+    )
+}
+
+fn section_html(section: &EmailSection) -> String {
+    format!(
+        r#"<h3>{}</h3>
+        <ul style="font-size: 14px;">{}</ul>"#,
+        escape_html(&section.title),
+        html_list(&section.items)
+    )
+}
+
+/// One numbered thought, indented `depth` levels.
+fn thought_html(number: usize, thought: &Thought, depth: usize) -> String {
+    let analysis = thought
+        .analysis()
+        .map(|analysis| {
+            format!(
+                r#"<div style="white-space: pre-wrap; color: #444;"><em>Analysis:</em>
+{}</div>"#,
+                escape_html(analysis)
+            )
+        })
+        .unwrap_or_default();
+    let score = thought.score().map(score_html).unwrap_or_default();
+    let answer = thought
+        .answer()
+        .map(|answer| {
+            format!(
+                r#"<div style="white-space: pre-wrap; color: #444;"><em>Answer:</em>
+{}</div>"#,
+                escape_html(answer)
+            )
+        })
+        .unwrap_or_default();
+    let progress = thought
+        .progress()
+        .map(|progress| format!("<p><em>Progress:</em> {}</p>", progress))
+        .unwrap_or_default();
+    format!(
+        r#"<div style="font-size: 14px; margin-bottom: 20px; margin-left: {}px;">
+                <strong>{}. {}{}: </strong>
                 <p>{}</p>
+                {}{}{}{}
                 <hr/>
                 </div>"#,
-                        i + 1,
-                        thought.thought_type,
-                        thought.content
-                    )
-                })
-                .collect::<String>();
+        depth * 24,
+        number,
+        thought.thought_type,
+        if thought.starred { " &#9733;" } else { "" },
+        escape_html(&thought.content),
+        progress,
+        score,
+        answer,
+        analysis
+    )
+}
+
+impl ThoughtsEmailBody<'_> {
+    pub fn html(&self) -> String {
+        let leading =
+            pinned_html(self.pinned) + &self.leading.iter().map(section_html).collect::<String>();
+        let sections = self.sections.iter().map(section_html).collect::<String>();
+        if self.thoughts.is_empty() {
+            format!(
+                r#"<html><body style="font-size: 16px;">
+    <h2>Weekly Thoughts Summary</h2>
+    {}
+    <p>No thoughts recorded this week.</p>
+    {}
+    </body></html>"#,
+                leading, sections
+            )
+        } else {
+            let thoughts_section = self.thoughts_html();
 
             format!(
                 r#"<html><body style="font-size: 16px;">
         <h2>Weekly Thoughts Summary</h2>
         {}
+        {}
+        {}
         <p>End of weekly roundup</p>
         </body></html>"#,
-                thoughts_section
+                leading, thoughts_section, sections
             )
-        };
+        }
+    }
+}
 
-        Body::new(body_text)
+impl IntoBody for ThoughtsEmailBody<'_> {
+    fn into_body(self, _encoding: Option<ContentTransferEncoding>) -> Body {
+        Body::new(self.html())
     }
 }
 
@@ -95,7 +573,7 @@ mod tests {
         assert_eq!(*thought.id(), 1);
         assert!(matches!(thought.thought_type(), ThoughtType::Notes));
         assert_eq!(thought.content(), "Test content");
-        assert_eq!(thought.reviewed(), false);
+        assert!(!thought.reviewed());
     }
 
     #[test]
@@ -104,7 +582,17 @@ mod tests {
         assert_eq!(*thought.id(), 42);
         assert!(matches!(thought.thought_type(), ThoughtType::Project));
         assert_eq!(thought.content(), "Project idea");
-        assert_eq!(thought.reviewed(), true);
+        assert!(thought.reviewed());
+    }
+
+    #[test]
+    fn test_thought_tags_and_created_at() {
+        let thought = Thought::new(7, ThoughtType::Notes, "Tagged".to_string(), false)
+            .with_tags(vec!["rust".to_string(), "cli".to_string()])
+            .with_created_at("2025-03-14 09:26:53".to_string());
+        assert_eq!(thought.tags(), ["rust", "cli"]);
+        assert_eq!(thought.created_at(), "2025-03-14 09:26:53");
+        assert_eq!(thought.created_on(), "2025-03-14");
     }
 
     #[test]
@@ -168,4 +656,176 @@ mod tests {
         let _body = email_body.into_body(None);
         // Body is created successfully if we reach here
     }
+
+    #[test]
+    fn test_thoughts_email_body_includes_analysis() {
+        let thoughts = vec![
+            Thought::new(1, ThoughtType::Project, "Idea".to_string(), false)
+                .with_analysis(Some("Worth <building>".to_string())),
+            Thought::new(2, ThoughtType::Notes, "Plain note".to_string(), false),
+        ];
+
+        let html = ThoughtsEmailBody::new(&thoughts).html();
+
+        assert!(html.contains("Worth &lt;building&gt;"));
+        assert_eq!(html.matches("Analysis:").count(), 1);
+    }
+
+    #[test]
+    fn test_thoughts_email_body_escapes_content() {
+        let thoughts = vec![Thought::new(
+            1,
+            ThoughtType::Notes,
+            "Use Vec<String> & friends".to_string(),
+            false,
+        )];
+
+        let html = ThoughtsEmailBody::new(&thoughts).html();
+
+        assert!(html.contains("<p>Use Vec&lt;String&gt; &amp; friends</p>"));
+    }
+
+    #[test]
+    fn test_thoughts_email_body_includes_score() {
+        let score = IdeaScore::new(
+            "Habit tracker".to_string(),
+            "hobby".to_string(),
+            "2 weekends".to_string(),
+            4,
+            vec!["Crowded market".to_string()],
+            vec!["Sketch the schema".to_string()],
+        );
+        let thoughts = vec![
+            Thought::new(1, ThoughtType::Project, "Idea".to_string(), false)
+                .with_score(Some(score)),
+        ];
+
+        let html = ThoughtsEmailBody::new(&thoughts).html();
+
+        assert!(html.contains("Novelty: <strong>4/10</strong>"));
+        assert!(html.contains("<li>Crowded market</li>"));
+        assert!(html.contains("<li>Sketch the schema</li>"));
+    }
+
+    #[test]
+    fn test_thoughts_email_body_includes_sections() {
+        let thoughts = vec![Thought::new(
+            1,
+            ThoughtType::Notes,
+            "Note".to_string(),
+            false,
+        )];
+
+        let html = ThoughtsEmailBody::new(&thoughts)
+            .with_sections(vec![
+                EmailSection::new("Seen before", vec!["#1 <ok>".to_string()]),
+                EmailSection::new("Empty", vec![]),
+            ])
+            .html();
+
+        assert!(html.contains("<h3>Seen before</h3>"));
+        assert!(html.contains("<li>#1 &lt;ok&gt;</li>"));
+        assert!(!html.contains("Empty"));
+    }
+
+    #[test]
+    fn test_thoughts_email_body_leading_sections_come_first() {
+        let thoughts = vec![Thought::new(
+            1,
+            ThoughtType::Notes,
+            "Note".to_string(),
+            false,
+        )];
+
+        let html = ThoughtsEmailBody::new(&thoughts)
+            .with_leading_sections(vec![EmailSection::new(
+                "Overdue todos",
+                vec!["#2 Pay rent".to_string()],
+            )])
+            .html();
+
+        assert!(html.find("Overdue todos").unwrap() < html.find("Note").unwrap());
+    }
+
+    #[test]
+    fn test_thoughts_email_body_groups_by_theme() {
+        let thoughts = vec![
+            Thought::new(1, ThoughtType::Project, "Habit app".to_string(), false),
+            Thought::new(2, ThoughtType::Notes, "Bread recipe".to_string(), false),
+            Thought::new(3, ThoughtType::Todo, "Streak goals".to_string(), false),
+        ];
+
+        let html = ThoughtsEmailBody::new(&thoughts)
+            .with_themes(vec![Theme::new("Habits".to_string(), vec![1, 3])])
+            .html();
+
+        let heading = html.find("<h3>Habits</h3>").unwrap();
+        let first = html.find("1. Project").unwrap();
+        let second = html.find("2. Todo").unwrap();
+        let ungrouped = html.find("3. Notes").unwrap();
+        assert!(heading < first && first < second && second < ungrouped);
+    }
+
+    #[test]
+    fn test_thoughts_email_body_indents_sub_thoughts() {
+        let mut project = Thought::new(1, ThoughtType::Project, "Garden".to_string(), false);
+        project.set_progress(Progress::new(1, 2));
+        let thoughts = vec![
+            project,
+            Thought::new(2, ThoughtType::Todo, "Buy seeds".to_string(), false).with_parent(Some(1)),
+            Thought::new(3, ThoughtType::Todo, "Sow".to_string(), false).with_parent(Some(2)),
+            Thought::new(4, ThoughtType::Todo, "Water".to_string(), false).with_parent(Some(9)),
+        ];
+
+        let html = ThoughtsEmailBody::new(&thoughts).html();
+
+        assert!(html.contains("<em>Progress:</em> 1/2 done (50%)"));
+        assert_eq!(html.matches("margin-left: 0px;").count(), 2);
+        assert_eq!(html.matches("margin-left: 24px;").count(), 1);
+        assert_eq!(html.matches("margin-left: 48px;").count(), 1);
+    }
+
+    #[test]
+    fn test_thoughts_email_body_indents_within_each_theme() {
+        let thoughts = vec![
+            Thought::new(1, ThoughtType::Project, "Garden".to_string(), false),
+            Thought::new(2, ThoughtType::Todo, "Buy seeds".to_string(), false).with_parent(Some(1)),
+            Thought::new(3, ThoughtType::Todo, "Sow".to_string(), false).with_parent(Some(2)),
+        ];
+
+        let html = ThoughtsEmailBody::new(&thoughts)
+            .with_themes(vec![Theme::new("Shopping".to_string(), vec![2, 3])])
+            .html();
+
+        assert_eq!(html.matches("margin-left: 0px;").count(), 2);
+        assert_eq!(html.matches("margin-left: 24px;").count(), 1);
+        assert!(!html.contains("margin-left: 48px;"));
+    }
+
+    #[test]
+    fn test_thoughts_email_body_pinned_section_comes_first() {
+        let pinned = vec![
+            Thought::new(7, ThoughtType::Notes, "Tax <deadlines>".to_string(), true)
+                .with_pinned(true),
+        ];
+        let thoughts = vec![
+            Thought::new(1, ThoughtType::Project, "Garden".to_string(), false).with_starred(true),
+        ];
+
+        let html = ThoughtsEmailBody::new(&thoughts)
+            .with_pinned(&pinned)
+            .with_leading_sections(vec![EmailSection::new(
+                "Overdue todos",
+                vec!["#2 Pay rent".to_string()],
+            )])
+            .html();
+
+        assert!(html.contains("<strong>#7 Notes:</strong> Tax &lt;deadlines&gt;</li>"));
+        assert!(html.find("<h3>Pinned</h3>").unwrap() < html.find("Overdue todos").unwrap());
+        assert!(html.contains("1. Project &#9733;: "));
+
+        let html = ThoughtsEmailBody::new(&[]).with_pinned(&pinned).html();
+        assert!(html.contains("<h3>Pinned</h3>"));
+        assert!(html.contains("No thoughts recorded this week."));
+    }
 }
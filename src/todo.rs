@@ -0,0 +1,223 @@
+use chrono::{Datelike, Days, Local, Months, NaiveDate, Weekday};
+use clap::ValueEnum;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+use strum_macros::{Display, EnumString};
+
+/// Where a todo is in its lifecycle.
+#[derive(Display, EnumString, Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum TodoStatus {
+    #[default]
+    Open,
+    InProgress,
+    Done,
+}
+
+impl FromSql for TodoStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        <TodoStatus as FromStr>::from_str(value.as_str()?)
+            .map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+#[derive(Display, EnumString, Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl FromSql for Priority {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        <Priority as FromStr>::from_str(value.as_str()?)
+            .map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+/// Status, due date and priority of a `Todo` thought.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct TodoDetails {
+    status: TodoStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    due: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<Priority>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completed_at: Option<String>,
+}
+
+impl TodoDetails {
+    pub fn new(
+        status: TodoStatus,
+        due: Option<String>,
+        priority: Option<Priority>,
+        completed_at: Option<String>,
+    ) -> TodoDetails {
+        TodoDetails {
+            status,
+            due,
+            priority,
+            completed_at,
+        }
+    }
+    pub fn status(&self) -> TodoStatus {
+        self.status
+    }
+    /// YYYY-MM-DD in local time.
+    pub fn due(&self) -> Option<&str> {
+        self.due.as_deref()
+    }
+    pub fn priority(&self) -> Option<Priority> {
+        self.priority
+    }
+    /// UTC, YYYY-MM-DD HH:MM:SS, set while the todo is done.
+    pub fn completed_at(&self) -> Option<&str> {
+        self.completed_at.as_deref()
+    }
+    /// Whether the todo is unfinished and its due date is before `today`.
+    pub fn overdue(&self, today: NaiveDate) -> bool {
+        self.status != TodoStatus::Done
+            && self
+                .due
+                .as_deref()
+                .is_some_and(|due| due < date(today).as_str())
+    }
+}
+
+/// How many of the todos under a thought are done, counting every level
+/// below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Progress {
+    done: u32,
+    total: u32,
+}
+
+impl Progress {
+    pub fn new(done: u32, total: u32) -> Progress {
+        Progress { done, total }
+    }
+    pub fn done(&self) -> u32 {
+        self.done
+    }
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+    /// Whole percent done, rounded down so 100 means every todo is done.
+    pub fn percent(&self) -> u32 {
+        (self.done * 100).checked_div(self.total).unwrap_or(0)
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{} done ({}%)", self.done, self.total, self.percent())
+    }
+}
+
+/// A date as stored in the database.
+pub fn date(day: NaiveDate) -> String {
+    day.format("%Y-%m-%d").to_string()
+}
+
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+/// The next `weekday` strictly after `today`.
+fn next_weekday(today: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let ahead = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+    today + Days::new(if ahead == 0 { 7 } else { ahead as u64 })
+}
+
+/// Parses a due date relative to `today`: `YYYY-MM-DD`, `today`,
+/// `tomorrow`, a weekday such as `friday` or `next fri` (the first one after
+/// today), `next week`, `next month`, or `in 3 days` / `in 2 weeks` /
+/// `in 1 month`.
+pub fn parse_due(text: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let text = text.trim().to_lowercase();
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let parsed = match words.as_slice() {
+        ["today"] => Some(today),
+        ["tomorrow"] => today.checked_add_days(Days::new(1)),
+        ["next", "week"] => today.checked_add_days(Days::new(7)),
+        ["next", "month"] => today.checked_add_months(Months::new(1)),
+        ["in", count, unit] => {
+            count
+                .parse::<u32>()
+                .ok()
+                .and_then(|n| match unit.trim_end_matches('s') {
+                    "day" => today.checked_add_days(Days::new(n.into())),
+                    "week" => today.checked_add_days(Days::new(u64::from(n) * 7)),
+                    "month" => today.checked_add_months(Months::new(n)),
+                    _ => None,
+                })
+        }
+        ["next", day] | [day] => match Weekday::from_str(day) {
+            Ok(weekday) => Some(next_weekday(today, weekday)),
+            Err(_) => NaiveDate::parse_from_str(day, "%Y-%m-%d").ok(),
+        },
+        _ => None,
+    };
+    parsed.ok_or_else(|| {
+        format!(
+            "could not read due date '{}', try YYYY-MM-DD, tomorrow, friday or in 3 days",
+            text
+        )
+    })
+}
+
+/// Command line parser for due dates, relative to the local date.
+pub fn parse_due_arg(text: &str) -> Result<String, String> {
+    parse_due(text, today()).map(date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_due_natural_language() {
+        // A Wednesday.
+        let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let due = |text: &str| parse_due(text, today).map(date);
+
+        assert_eq!(due("2025-03-01").unwrap(), "2025-03-01");
+        assert_eq!(due("Today").unwrap(), "2025-01-15");
+        assert_eq!(due("tomorrow").unwrap(), "2025-01-16");
+        assert_eq!(due("next friday").unwrap(), "2025-01-17");
+        assert_eq!(due("fri").unwrap(), "2025-01-17");
+        assert_eq!(due("wednesday").unwrap(), "2025-01-22");
+        assert_eq!(due("next week").unwrap(), "2025-01-22");
+        assert_eq!(due("in 3 days").unwrap(), "2025-01-18");
+        assert_eq!(due("in 2 weeks").unwrap(), "2025-01-29");
+        assert_eq!(due("next month").unwrap(), "2025-02-15");
+        assert_eq!(due("in 1 month").unwrap(), "2025-02-15");
+        assert!(due("someday").is_err());
+        assert!(due("in three days").is_err());
+    }
+
+    #[test]
+    fn test_progress_percent_rounds_down() {
+        assert_eq!(Progress::new(2, 3).to_string(), "2/3 done (66%)");
+        assert_eq!(Progress::new(3, 3).percent(), 100);
+        assert_eq!(Progress::new(0, 0).percent(), 0);
+    }
+
+    #[test]
+    fn test_overdue_ignores_done_and_undated() {
+        let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let todo =
+            |status, due: Option<&str>| TodoDetails::new(status, due.map(String::from), None, None);
+
+        assert!(todo(TodoStatus::Open, Some("2025-01-14")).overdue(today));
+        assert!(!todo(TodoStatus::InProgress, Some("2025-01-15")).overdue(today));
+        assert!(!todo(TodoStatus::Done, Some("2025-01-01")).overdue(today));
+        assert!(!todo(TodoStatus::Open, None).overdue(today));
+    }
+}
@@ -0,0 +1,179 @@
+use crate::db_operations::{current_month, usage_totals};
+use crate::errors::AppError;
+use crate::reader_config::UsageConfig;
+use rusqlite::Connection;
+use std::io::Write;
+
+/// Tokens sent to and generated by a provider.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenUsage {
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn new(input_tokens: u64, output_tokens: u64) -> TokenUsage {
+        TokenUsage {
+            input_tokens,
+            output_tokens,
+        }
+    }
+    pub fn input_tokens(&self) -> u64 {
+        self.input_tokens
+    }
+    pub fn output_tokens(&self) -> u64 {
+        self.output_tokens
+    }
+}
+
+/// Usage summed over the calls to one provider and model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageTotal {
+    provider: String,
+    model: String,
+    calls: u64,
+    tokens: TokenUsage,
+}
+
+impl UsageTotal {
+    pub fn new(provider: String, model: String, calls: u64, tokens: TokenUsage) -> UsageTotal {
+        UsageTotal {
+            provider,
+            model,
+            calls,
+            tokens,
+        }
+    }
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+    pub fn tokens(&self) -> &TokenUsage {
+        &self.tokens
+    }
+}
+
+/// Estimated spend this month, from usage without a configured price
+/// counting as free.
+pub fn month_cost(conn: &Connection, config: &UsageConfig) -> Result<f64, AppError> {
+    let month = current_month(conn)?;
+    Ok(usage_totals(conn, Some(&month))?
+        .iter()
+        .filter_map(|t| config.cost(t.model(), t.tokens()))
+        .sum())
+}
+
+/// The spend and budget when this month's estimated spend has reached the
+/// monthly budget.
+pub fn over_budget(
+    conn: &Connection,
+    config: &UsageConfig,
+) -> Result<Option<(f64, f64)>, AppError> {
+    let Some(budget) = config.monthly_budget() else {
+        return Ok(None);
+    };
+    let spent = month_cost(conn, config)?;
+    Ok((spent >= budget).then_some((spent, budget)))
+}
+
+/// Prints calls, tokens and estimated cost per provider and model for one
+/// month (`YYYY-MM`, default the current one) or, with `all`, all time.
+pub fn report<W: Write>(
+    conn: &Connection,
+    config: &UsageConfig,
+    month: Option<&str>,
+    all: bool,
+    out: &mut W,
+) -> Result<(), AppError> {
+    let month = match month {
+        Some(month) => month.to_string(),
+        None => current_month(conn)?,
+    };
+    let totals = usage_totals(conn, (!all).then_some(month.as_str()))?;
+    writeln!(
+        out,
+        "Usage for {}",
+        if all { "all time" } else { month.as_str() }
+    )?;
+    if totals.is_empty() {
+        writeln!(out, "No AI calls recorded")?;
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "{:<8} {:<28} {:>6} {:>10} {:>10} {:>10}",
+        "Provider", "Model", "Calls", "Input", "Output", "Cost"
+    )?;
+    let mut total_cost = 0.0;
+    for total in &totals {
+        let cost = config.cost(total.model(), total.tokens());
+        total_cost += cost.unwrap_or(0.0);
+        writeln!(
+            out,
+            "{:<8} {:<28} {:>6} {:>10} {:>10} {:>10}",
+            total.provider(),
+            total.model(),
+            total.calls(),
+            total.tokens().input_tokens(),
+            total.tokens().output_tokens(),
+            cost.map_or_else(|| "no price".to_string(), |c| format!("${:.4}", c))
+        )?;
+    }
+    writeln!(out, "Estimated total: ${:.4}", total_cost)?;
+    if let Some(budget) = config.monthly_budget().filter(|_| !all) {
+        writeln!(out, "Monthly budget: ${:.2}", budget)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_operations::{save_usage, setup_db};
+
+    fn usage_config(budget: f64) -> UsageConfig {
+        toml::from_str(&format!(
+            r#"
+            monthly_budget = {}
+            [prices."gpt-4o-mini"]
+            input = 1.0
+            output = 2.0
+        "#,
+            budget
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_budget_and_report() {
+        let conn = setup_db(":memory:").unwrap();
+        save_usage(
+            &conn,
+            "OpenAI",
+            "gpt-4o-mini",
+            &TokenUsage::new(1_000_000, 500_000),
+        )
+        .unwrap();
+        save_usage(&conn, "Ollama", "llama3.2", &TokenUsage::new(10, 5)).unwrap();
+
+        assert_eq!(month_cost(&conn, &usage_config(5.0)).unwrap(), 2.0);
+        assert!(over_budget(&conn, &usage_config(5.0)).unwrap().is_none());
+        assert_eq!(
+            over_budget(&conn, &usage_config(1.5)).unwrap(),
+            Some((2.0, 1.5))
+        );
+
+        let mut out = Vec::new();
+        report(&conn, &usage_config(5.0), None, false, &mut out).unwrap();
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains("no price"));
+        assert!(output.contains("$2.0000"));
+        assert!(output.contains("Estimated total: $2.0000"));
+        assert!(output.contains("Monthly budget: $5.00"));
+    }
+}
@@ -1,35 +1,103 @@
+use crate::todo::{Priority, parse_due_arg};
 use clap::{Parser, ValueEnum};
 use rusqlite::types::{FromSql, FromSqlError};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use strum_macros::{Display, EnumString};
 
 #[derive(Parser, Debug)]
 pub struct Args {
-    #[arg(long, value_enum)]
-    thought_type: ThoughtType,
+    #[arg(long, value_enum, required_unless_present = "auto_type")]
+    thought_type: Option<ThoughtType>,
     #[arg(short = 'c', long)]
     content: String,
+    #[arg(short, long, value_delimiter = ',')]
+    tags: Vec<String>,
+    /// Due date of a todo: YYYY-MM-DD, tomorrow, friday, next week, in 3 days
+    #[arg(long, value_parser = parse_due_arg)]
+    due: Option<String>,
+    /// Priority of a todo
+    #[arg(long, value_enum)]
+    priority: Option<Priority>,
+    /// Id of the thought to file this one under, e.g. a todo's project
+    #[arg(long)]
+    parent: Option<i32>,
+    /// Ask the configured AI provider to suggest a type and tags
+    #[arg(long)]
+    auto_type: bool,
+    /// Config file with the AI provider settings, used by --auto-type
+    #[arg(long, default_value = "config.toml")]
+    config: PathBuf,
 }
 
 impl Args {
+    /// The chosen type. Thoughts captured with only `--auto-type` are filed
+    /// under `Misc` until a suggestion is accepted.
     pub fn thought_type(&self) -> &ThoughtType {
-        &self.thought_type
+        self.thought_type.as_ref().unwrap_or(&ThoughtType::Misc)
+    }
+    pub fn auto_type(&self) -> bool {
+        self.auto_type
+    }
+    pub fn config(&self) -> &Path {
+        &self.config
     }
     pub fn content(&self) -> &str {
         &self.content
     }
+    /// The tags as given, trimmed, without empty or repeated ones.
+    pub fn tags(&self) -> Vec<&str> {
+        let mut tags = Vec::new();
+        for tag in self.tags.iter().map(|t| t.trim()) {
+            if !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        tags
+    }
+    /// YYYY-MM-DD, already resolved from natural language.
+    pub fn due(&self) -> Option<&str> {
+        self.due.as_deref()
+    }
+    pub fn priority(&self) -> Option<Priority> {
+        self.priority
+    }
+    pub fn parent(&self) -> Option<i32> {
+        self.parent
+    }
 
     /// Create Args for testing purposes
     /// Only available in test builds
     pub fn new_for_test(thought_type: ThoughtType, content: String) -> Self {
         Args {
-            thought_type,
+            thought_type: Some(thought_type),
             content,
+            tags: Vec::new(),
+            due: None,
+            priority: None,
+            parent: None,
+            auto_type: false,
+            config: PathBuf::from("config.toml"),
         }
     }
+
+    /// Attach tags to test Args
+    pub fn with_tags(mut self, tags: &[&str]) -> Self {
+        self.tags = tags.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    /// File test Args under a parent thought
+    pub fn with_parent(mut self, parent: i32) -> Self {
+        self.parent = Some(parent);
+        self
+    }
 }
 
-#[derive(Display, Debug, Clone, ValueEnum, EnumString)]
+#[derive(
+    Display, Debug, Clone, PartialEq, Eq, Hash, ValueEnum, EnumString, Serialize, Deserialize,
+)]
 #[strum(ascii_case_insensitive)]
 pub enum ThoughtType {
     Notes,
@@ -150,4 +218,75 @@ mod tests {
         assert!(matches!(args.thought_type(), ThoughtType::Notes));
         assert_eq!(args.content(), "Test content");
     }
+
+    #[test]
+    fn test_args_parse_tags() {
+        let args = Args::try_parse_from([
+            "thought",
+            "--thought-type",
+            "project",
+            "-c",
+            "Tagged idea",
+            "--tags",
+            "rust,cli",
+        ])
+        .unwrap();
+        assert_eq!(args.tags(), ["rust", "cli"]);
+    }
+
+    #[test]
+    fn test_args_tags_are_trimmed_and_deduplicated() {
+        let args = Args::try_parse_from([
+            "thought",
+            "--thought-type",
+            "project",
+            "-c",
+            "Tagged idea",
+            "--tags",
+            "rust, cli,,rust , ",
+        ])
+        .unwrap();
+        assert_eq!(args.tags(), ["rust", "cli"]);
+    }
+
+    #[test]
+    fn test_args_auto_type_without_thought_type() {
+        let args = Args::try_parse_from(["thought", "--auto-type", "-c", "Unsorted idea"]).unwrap();
+        assert!(args.auto_type());
+        assert!(matches!(args.thought_type(), ThoughtType::Misc));
+    }
+
+    #[test]
+    fn test_args_parse_due_and_priority() {
+        let args = Args::try_parse_from([
+            "thought",
+            "--thought-type",
+            "todo",
+            "-c",
+            "File taxes",
+            "--due",
+            "2025-04-15",
+            "--priority",
+            "high",
+        ])
+        .unwrap();
+        assert_eq!(args.due(), Some("2025-04-15"));
+        assert_eq!(args.priority(), Some(Priority::High));
+
+        let args = [
+            "thought",
+            "--thought-type",
+            "todo",
+            "-c",
+            "Later",
+            "--due",
+            "someday",
+        ];
+        assert!(Args::try_parse_from(args).is_err());
+    }
+
+    #[test]
+    fn test_args_thought_type_required_without_auto_type() {
+        assert!(Args::try_parse_from(["thought", "-c", "Untyped"]).is_err());
+    }
 }
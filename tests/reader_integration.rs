@@ -1,7 +1,6 @@
 #[cfg(feature = "reader")]
 mod reader_tests {
     use thought::db_operations::{read, setup_db};
-    use thought::writer_config::ThoughtType;
 
     #[test]
     fn test_read_end_to_end() {
@@ -132,3 +131,944 @@ mod reader_tests {
         assert_eq!(read_types, expected_types);
     }
 }
+
+#[cfg(feature = "reader")]
+mod ai_client_tests {
+    use clap::Parser;
+    use thought::client::{Session, get_response, send_streaming_request};
+    use thought::commands;
+    use thought::db_operations::{get_thought, pending_suggestions, read, setup_db, usage_totals};
+    use thought::digest::{analyse, answer_questions, still_open};
+    use thought::embeddings;
+    use thought::reader_config::{AIClient, AIClientConfig, Args, Config, RetryConfig};
+    use thought::themes;
+    use thought::usage::{TokenUsage, over_budget};
+    use thought::writer_config::ThoughtType;
+    use wiremock::matchers::{body_partial_json, body_string_contains, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn config(ai_client: &str, base_url: &str, extra: &str) -> Config {
+        let toml_content = format!(
+            r#"
+            [ai_client_config]
+            ai_client = "{}"
+            model = "test-model"
+            base_url = "{}"
+
+            [email_config]
+            sender_email = "test@test.com"
+            receiver_email = "test@test.com"
+            app_password = "pass"
+            relay = "smtp.test.com"
+            name = "Test"
+
+            {}
+        "#,
+            ai_client, base_url, extra
+        );
+        Args::parse_config(&toml_content).unwrap()
+    }
+
+    fn ai_config(ai_client: &str, base_url: &str) -> AIClientConfig {
+        config(ai_client, base_url, "").ai_client_config()
+    }
+
+    fn retry_config(max_attempts: u32) -> RetryConfig {
+        toml::from_str(&format!(
+            "max_attempts = {}\ninitial_backoff_ms = 1\nmax_backoff_ms = 5",
+            max_attempts
+        ))
+        .unwrap()
+    }
+
+    fn chat_response(content: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "choices": [{"message": {"role": "assistant", "content": content}}]
+        }))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_ollama_provider_against_mock_server() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "model": "test-model",
+                "messages": [{"role": "user"}]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{"message": {"role": "assistant", "content": "local analysis"}}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ai_config("Ollama", &server.uri());
+        let response = tokio::task::spawn_blocking(move || {
+            get_response(&config, &retry_config(1), "Offline idea")
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(response, "local analysis");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_api_key_headers_per_provider() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/models/test-model:generateContent"))
+            .and(header("x-goog-api-key", "gemini-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "candidates": [{"content": {"parts": [{"text": "from gemini"}]}}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(header("Authorization", "Bearer openai-key"))
+            .respond_with(chat_response("from openai"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let keyed = |ai_client: &str, key: &str| {
+            let toml_content = format!(
+                r#"
+                [ai_client_config]
+                ai_client = "{}"
+                bearer_token = "{}"
+                model = "test-model"
+                base_url = "{}"
+
+                [email_config]
+                sender_email = "test@test.com"
+                receiver_email = "test@test.com"
+                app_password = "pass"
+                relay = "smtp.test.com"
+                name = "Test"
+            "#,
+                ai_client,
+                key,
+                server.uri()
+            );
+            Args::parse_config(&toml_content)
+                .unwrap()
+                .ai_client_config()
+        };
+        let gemini = keyed("Gemini", "gemini-key");
+        let openai = keyed("OpenAI", "openai-key");
+        let responses = tokio::task::spawn_blocking(move || {
+            [
+                get_response(&gemini, &retry_config(1), "Idea").unwrap(),
+                get_response(&openai, &retry_config(1), "Idea").unwrap(),
+            ]
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(responses, ["from gemini", "from openai"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_provider_error_status_is_reported() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("bad key"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ai_config("OpenAI", &server.uri());
+        let result =
+            tokio::task::spawn_blocking(move || get_response(&config, &retry_config(3), "Idea"))
+                .await
+                .unwrap();
+
+        assert!(result.unwrap_err().to_string().contains("bad key"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_per_thought_analysis_is_stored_for_each_thought() {
+        let server = MockServer::start().await;
+        for (idea, analysis) in [
+            ("Habit tracker", "Analysis A"),
+            ("Recipe app", "Analysis B"),
+        ] {
+            Mock::given(method("POST"))
+                .and(body_string_contains(idea))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "choices": [{"message": {"content": analysis}}]
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let config = config("OpenAI", &server.uri(), "[prompts]\nmode = \"per_thought\"");
+        let (thoughts, conn) = tokio::task::spawn_blocking(move || {
+            let conn = setup_db(":memory:").unwrap();
+            for idea in ["Habit tracker", "Recipe app"] {
+                conn.execute(
+                    "INSERT INTO thoughts (type, content) VALUES ('Project', ?)",
+                    [idea],
+                )
+                .unwrap();
+            }
+            conn.execute(
+                "INSERT INTO thoughts (type, content) VALUES ('Notes', 'Not analysed')",
+                [],
+            )
+            .unwrap();
+            let mut thoughts = read(&conn).unwrap();
+            analyse(&conn, &config, &mut thoughts).unwrap();
+            (thoughts, conn)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(thoughts[0].analysis(), Some("Analysis A"));
+        assert_eq!(thoughts[1].analysis(), Some("Analysis B"));
+        assert_eq!(thoughts[2].analysis(), None);
+        let stored = get_thought(&conn, 2).unwrap().unwrap();
+        assert_eq!(stored.analysis(), Some("Analysis B"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_failed_analysis_skips_only_that_thought() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("Habit tracker"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("bad request"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("Recipe app"))
+            .respond_with(chat_response("Analysis B"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = config("OpenAI", &server.uri(), "[prompts]\nmode = \"per_thought\"");
+        let thoughts = tokio::task::spawn_blocking(move || {
+            let conn = setup_db(":memory:").unwrap();
+            for idea in ["Habit tracker", "Recipe app"] {
+                conn.execute(
+                    "INSERT INTO thoughts (type, content) VALUES ('Project', ?)",
+                    [idea],
+                )
+                .unwrap();
+            }
+            let mut thoughts = read(&conn).unwrap();
+            analyse(&conn, &config, &mut thoughts).unwrap();
+            thoughts
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(thoughts[0].analysis(), None);
+        assert_eq!(thoughts[1].analysis(), Some("Analysis B"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_retries_flaky_server_until_success() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503).set_body_string("overloaded"))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(chat_response("finally"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ai_config("OpenAI", &server.uri());
+        let response =
+            tokio::task::spawn_blocking(move || get_response(&config, &retry_config(3), "Idea"))
+                .await
+                .unwrap();
+
+        assert_eq!(response.unwrap(), "finally");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_honours_retry_after_on_rate_limit() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(chat_response("after waiting"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ai_config("OpenAI", &server.uri());
+        let retry: RetryConfig = toml::from_str("max_attempts = 2\nmax_backoff_ms = 5000").unwrap();
+        let started = std::time::Instant::now();
+        let response = tokio::task::spawn_blocking(move || get_response(&config, &retry, "Idea"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.unwrap(), "after waiting");
+        assert!(started.elapsed() >= std::time::Duration::from_secs(1));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_gives_up_after_max_attempts() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503).set_body_string("still down"))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let config = ai_config("OpenAI", &server.uri());
+        let result =
+            tokio::task::spawn_blocking(move || get_response(&config, &retry_config(3), "Idea"))
+                .await
+                .unwrap();
+
+        assert!(result.unwrap_err().to_string().contains("still down"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_structured_mode_stores_validated_score() {
+        let server = MockServer::start().await;
+        let score = serde_json::json!({
+            "summary": "Offline-first notes app",
+            "scale": "side project",
+            "effort_estimate": "1 month",
+            "novelty_score": 6,
+            "risks": ["Sync conflicts"],
+            "next_steps": ["Prototype CRDT sync"]
+        });
+        Mock::given(method("POST"))
+            .and(path("/v1/models/test-model:generateContent"))
+            .and(body_partial_json(serde_json::json!({
+                "generationConfig": {"responseMimeType": "application/json"}
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "candidates": [{"content": {"parts": [{"text": score.to_string()}]}}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = config("Gemini", &server.uri(), "[prompts]\nmode = \"structured\"");
+        let stored = tokio::task::spawn_blocking(move || {
+            let conn = setup_db(":memory:").unwrap();
+            conn.execute(
+                "INSERT INTO thoughts (type, content) VALUES ('Project', 'Notes app')",
+                [],
+            )
+            .unwrap();
+            let mut thoughts = read(&conn).unwrap();
+            analyse(&conn, &config, &mut thoughts).unwrap();
+            get_thought(&conn, 1).unwrap().unwrap()
+        })
+        .await
+        .unwrap();
+
+        let stored_score = stored.score().unwrap();
+        assert_eq!(stored_score.novelty_score(), 6);
+        assert_eq!(stored_score.scale(), "side project");
+        assert_eq!(stored_score.risks(), ["Sync conflicts"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reclassify_stores_pending_suggestions() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_string_contains("Classify the thought"))
+            .respond_with(chat_response(
+                r#"{"thought_type": "Todo", "tags": ["Errands"]}"#,
+            ))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let toml_content = format!(
+            r#"
+            [ai_client_config]
+            ai_client = "Ollama"
+            base_url = "{}"
+
+            [email_config]
+            sender_email = "test@test.com"
+            receiver_email = "test@test.com"
+            app_password = "pass"
+            relay = "smtp.test.com"
+            name = "Test"
+        "#,
+            server.uri()
+        );
+        let output = tokio::task::spawn_blocking(move || {
+            let config_file = tempfile::NamedTempFile::new().unwrap();
+            std::fs::write(config_file.path(), toml_content).unwrap();
+            let args = Args::try_parse_from([
+                "thought",
+                "--config",
+                config_file.path().to_str().unwrap(),
+                "reclassify",
+            ])
+            .unwrap();
+            let conn = setup_db(":memory:").unwrap();
+            for (thought_type, content) in [
+                ("Misc", "Pick up dry cleaning"),
+                ("Misc", "Renew passport"),
+                ("Notes", "Rust 2024 edition notes"),
+            ] {
+                conn.execute(
+                    "INSERT INTO thoughts (type, content) VALUES (?, ?)",
+                    [thought_type, content],
+                )
+                .unwrap();
+            }
+            let mut out = Vec::new();
+            commands::run(args.command().unwrap(), &args, &conn, &mut out).unwrap();
+            let pending = pending_suggestions(&conn).unwrap();
+            (String::from_utf8(out).unwrap(), pending)
+        })
+        .await
+        .unwrap();
+
+        let (output, pending) = output;
+        assert_eq!(
+            output,
+            "#1: Misc -> Todo (tags: errands)\n#2: Misc -> Todo (tags: errands)\n"
+        );
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].thought_type(), &ThoughtType::Misc);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_embeddings_find_earlier_similar_thought() {
+        let server = MockServer::start().await;
+        for (word, vector) in [
+            ("habit", [1.0, 0.1, 0.0]),
+            ("streak", [0.9, 0.2, 0.0]),
+            ("Sourdough", [0.0, 0.0, 1.0]),
+        ] {
+            Mock::given(method("POST"))
+                .and(path("/v1/embeddings"))
+                .and(body_string_contains(word))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "data": [{"embedding": vector}]
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let config = config("Ollama", &server.uri(), "[embeddings]\nenabled = true");
+        let (similar, section) = tokio::task::spawn_blocking(move || {
+            let conn = setup_db(":memory:").unwrap();
+            for content in [
+                "A habit tracker app",
+                "Sourdough starter notes",
+                "Track daily streak goals",
+            ] {
+                conn.execute(
+                    "INSERT INTO thoughts (type, content) VALUES ('Project', ?)",
+                    [content],
+                )
+                .unwrap();
+            }
+            let similar = embeddings::similar(&conn, &config, 3, 1).unwrap();
+            let newest = get_thought(&conn, 3).unwrap().unwrap();
+            let section = embeddings::seen_before(&conn, &config, &[newest]).unwrap();
+            (similar, section)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(*similar[0].0.id(), 1);
+        assert!(similar[0].1 > 0.9);
+        assert_eq!(section.items().len(), 1);
+        assert!(section.items()[0].starts_with("#3 [Project] Track daily streak goals"));
+        assert!(section.items()[0].contains("is like #1 [Project] A habit tracker app"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_group_names_clusters_with_provider() {
+        let server = MockServer::start().await;
+        for (word, vector) in [
+            ("habit", [1.0, 0.1, 0.0]),
+            ("streak", [0.9, 0.2, 0.0]),
+            ("Sourdough", [0.0, 0.0, 1.0]),
+        ] {
+            Mock::given(method("POST"))
+                .and(path("/v1/embeddings"))
+                .and(body_string_contains(word))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "data": [{"embedding": vector}]
+                })))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_string_contains("Group 1"))
+            .respond_with(chat_response(r#"{"names": ["Habit tracking"]}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = config("Ollama", &server.uri(), "[digest]\ngroup_by_theme = true");
+        let themes = tokio::task::spawn_blocking(move || {
+            let conn = setup_db(":memory:").unwrap();
+            for content in [
+                "A habit tracker app",
+                "Sourdough starter notes",
+                "Track daily streak goals",
+            ] {
+                conn.execute(
+                    "INSERT INTO thoughts (type, content) VALUES ('Project', ?)",
+                    [content],
+                )
+                .unwrap();
+            }
+            let thoughts = read(&conn).unwrap();
+            themes::group(&conn, &config, &thoughts).unwrap()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(themes.len(), 2);
+        assert_eq!(themes[0].name(), "Habit tracking");
+        assert_eq!(themes[0].thought_ids(), [1, 3]);
+        assert_eq!(themes[1].name(), "Other thoughts");
+        assert_eq!(themes[1].thought_ids(), [2]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_questions_answered_and_listed_until_marked() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_string_contains("How do CRDTs merge?"))
+            .respond_with(chat_response("They merge states commutatively."))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = config("Ollama", &server.uri(), "[questions]\nanswer = true");
+        let (thoughts, section) = tokio::task::spawn_blocking(move || {
+            let conn = setup_db(":memory:").unwrap();
+            conn.execute(
+                "INSERT INTO thoughts (type, content, reviewed) VALUES ('Question', 'Old question', 1)",
+                [],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO thoughts (type, content) VALUES ('Question', 'How do CRDTs merge?')",
+                [],
+            )
+            .unwrap();
+            let mut thoughts = read(&conn).unwrap();
+            answer_questions(&conn, &config, &mut thoughts).unwrap();
+            assert_eq!(
+                get_thought(&conn, 2).unwrap().unwrap().answer(),
+                Some("They merge states commutatively.")
+            );
+            let section = still_open(&conn, &thoughts).unwrap();
+            (thoughts, section)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            thoughts[0].answer(),
+            Some("They merge states commutatively.")
+        );
+        assert_eq!(section.items().len(), 1);
+        assert!(section.items()[0].starts_with("#1 Old question"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_failed_answer_is_retried_next_run() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(chat_response("Use a vector clock."))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = config("Ollama", &server.uri(), "[questions]\nanswer = true");
+        tokio::task::spawn_blocking(move || {
+            let conn = setup_db(":memory:").unwrap();
+            conn.execute(
+                "INSERT INTO thoughts (type, content) VALUES ('Question', 'How to order events?')",
+                [],
+            )
+            .unwrap();
+            let mut thoughts = read(&conn).unwrap();
+            answer_questions(&conn, &config, &mut thoughts).unwrap();
+            assert_eq!(get_thought(&conn, 1).unwrap().unwrap().answer(), None);
+
+            let mut thoughts = read(&conn).unwrap();
+            assert!(thoughts.is_empty());
+            answer_questions(&conn, &config, &mut thoughts).unwrap();
+            assert_eq!(
+                get_thought(&conn, 1).unwrap().unwrap().answer(),
+                Some("Use a vector clock.")
+            );
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_analysis_records_token_usage_against_budget() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{"message": {"content": "Worth a weekend"}}],
+                "usage": {"prompt_tokens": 600000, "completion_tokens": 200000}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = config(
+            "OpenAI",
+            &server.uri(),
+            r#"
+            [prompts]
+            mode = "per_thought"
+            [usage]
+            monthly_budget = 0.5
+            [usage.prices."test-model"]
+            input = 0.5
+            output = 1.0
+            "#,
+        );
+        let (totals, over) = tokio::task::spawn_blocking(move || {
+            let conn = setup_db(":memory:").unwrap();
+            conn.execute(
+                "INSERT INTO thoughts (type, content) VALUES ('Project', 'Habit tracker')",
+                [],
+            )
+            .unwrap();
+            let mut thoughts = read(&conn).unwrap();
+            assert!(over_budget(&conn, config.usage()).unwrap().is_none());
+            analyse(&conn, &config, &mut thoughts).unwrap();
+            (
+                usage_totals(&conn, None).unwrap(),
+                over_budget(&conn, config.usage()).unwrap(),
+            )
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals[0].provider(), "OpenAI");
+        assert_eq!(totals[0].model(), "test-model");
+        assert_eq!(totals[0].calls(), 1);
+        assert_eq!(*totals[0].tokens(), TokenUsage::new(600000, 200000));
+        assert_eq!(over, Some((0.5, 0.5)));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_repeated_prompts_use_cached_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{"message": {"content": "Worth a weekend"}}],
+                "usage": {"prompt_tokens": 10, "completion_tokens": 5}
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let cached = config("OpenAI", &server.uri(), "[prompts]\nmode = \"per_thought\"");
+        let uncached = config(
+            "OpenAI",
+            &server.uri(),
+            "[prompts]\nmode = \"per_thought\"\n[cache]\nenabled = false",
+        );
+        let totals = tokio::task::spawn_blocking(move || {
+            let conn = setup_db(":memory:").unwrap();
+            conn.execute(
+                "INSERT INTO thoughts (type, content) VALUES ('Project', 'Habit tracker')",
+                [],
+            )
+            .unwrap();
+            for config in [&cached, &cached, &uncached] {
+                let mut thoughts = vec![get_thought(&conn, 1).unwrap().unwrap()];
+                analyse(&conn, config, &mut thoughts).unwrap();
+                assert_eq!(thoughts[0].analysis(), Some("Worth a weekend"));
+            }
+            usage_totals(&conn, None).unwrap()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(totals[0].calls(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_batch_mode_splits_truncated_batches() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("Alpha idea"))
+            .and(body_string_contains("Beta idea"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{"message": {"content": "Cut o"}, "finish_reason": "length"}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(chat_response("Fine"))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let config = config(
+            "OpenAI",
+            &server.uri(),
+            "[prompts]\nmode = \"batch\"\nmax_batch_tokens = 600",
+        );
+        tokio::task::spawn_blocking(move || {
+            let conn = setup_db(":memory:").unwrap();
+            for idea in ["Alpha idea", "Beta idea", &"Gamma idea ".repeat(150)] {
+                conn.execute(
+                    "INSERT INTO thoughts (type, content) VALUES ('Project', ?)",
+                    [idea],
+                )
+                .unwrap();
+            }
+            let mut thoughts = read(&conn).unwrap();
+            analyse(&conn, &config, &mut thoughts).unwrap();
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_truncated_single_thought_does_not_stop_analysis() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("Long idea"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{"message": {"content": "Cut o"}, "finish_reason": "length"}]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(chat_response("Fine"))
+            .mount(&server)
+            .await;
+
+        for mode in ["batch", "per_thought", "structured"] {
+            let config = config(
+                "OpenAI",
+                &server.uri(),
+                &format!("[prompts]\nmode = \"{}\"", mode),
+            );
+            let thoughts = tokio::task::spawn_blocking(move || {
+                let conn = setup_db(":memory:").unwrap();
+                for idea in ["Long idea", "Short idea"] {
+                    conn.execute(
+                        "INSERT INTO thoughts (type, content) VALUES ('Project', ?)",
+                        [idea],
+                    )
+                    .unwrap();
+                }
+                let mut thoughts = read(&conn).unwrap();
+                analyse(&conn, &config, &mut thoughts).unwrap();
+                thoughts
+            })
+            .await
+            .unwrap();
+
+            if mode == "per_thought" {
+                assert_eq!(thoughts[0].analysis(), None);
+                assert_eq!(thoughts[1].analysis(), Some("Fine"));
+            }
+        }
+    }
+
+    fn sse(events: &[&str]) -> ResponseTemplate {
+        let body: String = events.iter().map(|e| format!("data: {}\n\n", e)).collect();
+        ResponseTemplate::new(200).set_body_raw(body, "text/event-stream")
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_stream_broken_after_output_is_not_retried() {
+        let server = MockServer::start().await;
+        let mut broken =
+            b"data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Half \"}]}}]}\n\n"
+                .to_vec();
+        broken.extend_from_slice(b"data: \xff\xfe\n\n");
+        Mock::given(method("POST"))
+            .and(body_string_contains("Printed once"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(broken, "text/event-stream"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("Nothing printed"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(b"\xff\xfe\n".to_vec(), "text/event-stream"),
+            )
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let config = ai_config("Gemini", &server.uri());
+        tokio::task::spawn_blocking(move || {
+            let conn = setup_db(":memory:").unwrap();
+            let retry = retry_config(3);
+            let session = Session::new(&conn, config, &retry).with_streaming(true);
+            assert!(session.response("Printed once").is_err());
+            assert!(session.response("Nothing printed").is_err());
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_streaming_session_assembles_and_records_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/models/test-model:streamGenerateContent"))
+            .respond_with(sse(&[
+                r#"{"candidates":[{"content":{"parts":[{"text":"Weekend "}]}}]}"#,
+                r#"{"candidates":[{"content":{"parts":[{"text":"project"}]},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":11,"candidatesTokenCount":2}}"#,
+            ]))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = config("Gemini", &server.uri(), "");
+        let (text, totals) = tokio::task::spawn_blocking(move || {
+            let conn = setup_db(":memory:").unwrap();
+            let session =
+                Session::new(&conn, config.ai_client_config(), config.retry()).with_streaming(true);
+            let text = session.response("Habit tracker").unwrap();
+            (text, usage_totals(&conn, None).unwrap())
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(text, "Weekend project");
+        assert_eq!(*totals[0].tokens(), TokenUsage::new(11, 2));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_streamed_text_is_written_as_it_arrives() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(serde_json::json!({"stream": true})))
+            .respond_with(sse(&[
+                r#"{"choices":[{"delta":{"content":"Small "}}]}"#,
+                r#"{"choices":[{"delta":{"content":"scope"},"finish_reason":"stop"}]}"#,
+                "[DONE]",
+            ]))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let url = format!("{}/v1/chat/completions", server.uri());
+        let (printed, body) = tokio::task::spawn_blocking(move || {
+            let client = reqwest::blocking::Client::new();
+            let request = client
+                .post(url)
+                .body(r#"{"stream": true}"#)
+                .build()
+                .unwrap();
+            let mut out = Vec::new();
+            let body =
+                send_streaming_request(&client, request, &AIClient::Ollama, &mut out).unwrap();
+            (String::from_utf8(out).unwrap(), body)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(printed, "Small scope\n");
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["choices"][0]["message"]["content"], "Small scope");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_verbose_batch_analysis_is_not_streamed() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({"stream": true})))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(chat_response("Both ideas are feasible."))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let toml_content = format!(
+            r#"
+            [ai_client_config]
+            ai_client = "Ollama"
+            base_url = "{}"
+
+            [email_config]
+            sender_email = "test@test.com"
+            receiver_email = "test@test.com"
+            app_password = "pass"
+            relay = "smtp.test.com"
+            name = "Test"
+
+            [prompts]
+            mode = "batch"
+        "#,
+            server.uri()
+        );
+        tokio::task::spawn_blocking(move || {
+            let config_file = tempfile::NamedTempFile::new().unwrap();
+            std::fs::write(config_file.path(), toml_content).unwrap();
+            let args = Args::try_parse_from([
+                "thought",
+                "--verbose",
+                "--config",
+                config_file.path().to_str().unwrap(),
+            ])
+            .unwrap();
+            let config = args.config().unwrap();
+            assert!(config.verbose());
+            let conn = setup_db(":memory:").unwrap();
+            for content in ["Habit tracker", "Recipe app"] {
+                conn.execute(
+                    "INSERT INTO thoughts (type, content) VALUES ('Project', ?)",
+                    [content],
+                )
+                .unwrap();
+            }
+            let mut thoughts = read(&conn).unwrap();
+            analyse(&conn, &config, &mut thoughts).unwrap();
+        })
+        .await
+        .unwrap();
+    }
+}